@@ -0,0 +1,411 @@
+use serde::{Deserialize, Serialize};
+use url::Url;
+
+// every line of config.txt is `Key; value,`. the parser in lib.rs drops
+// spaces and glues comma separated parts together, so mistakes only show up
+// much later. this reads the same text strictly and reports every problem
+const REQUIRED: [&str; 5] = ["Departments", "Targets", "BaseUrl", "Reports", "Files"];
+const OPTIONAL: [&str; 12] = [
+    "Rules", "Sitemap", "UserAgent", "RetryAttempts", "RetryDelay", "RetryJitter", "RetryMaxDelay",
+    "RetryStatuses", "Concurrency", "RateLimit", "CrawlDepth", "CrawlPages",
+];
+const NUMBERS: [&str; 8] = [
+    "RetryAttempts", "RetryDelay", "RetryJitter", "RetryMaxDelay", "Concurrency", "RateLimit", "CrawlDepth", "CrawlPages",
+];
+// the only key whose value is a list
+const LIST: &str = "RetryStatuses";
+
+pub struct Problem {
+    pub line: usize,
+    pub column: usize,
+    pub message: String,
+    pub warning: bool,
+}
+
+impl Problem {
+    fn error(line: usize, column: usize, message: String) -> Self {
+        Self { line, column, message, warning: false }
+    }
+
+    fn warning(line: usize, column: usize, message: String) -> Self {
+        Self { line, column, message, warning: true }
+    }
+
+    pub fn describe(&self, file: &str) -> String {
+        let level = if self.warning { "warning" } else { "error" };
+        match self.line {
+            0 => format!("{file}: {level}: {}", self.message),
+            l => format!("{file}:{l}:{}: {level}: {}", self.column, self.message),
+        }
+    }
+}
+
+// `provided` are keys set some other way, like a command line flag
+pub fn check(text: &str, provided: &[&str]) -> Vec<Problem> {
+    let mut problems = Vec::new();
+    let mut seen: Vec<(String, usize)> = Vec::new();
+
+    for (at, line) in text.lines().enumerate() {
+        let number = at + 1;
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let Some(semicolon) = line.find(';') else {
+            problems.push(Problem::error(number, column(line, 0), String::from("expected `Key; value,`")));
+            continue;
+        };
+
+        let key = line[..semicolon].trim();
+        let key_column = column(line, line.find(key).unwrap_or(0));
+        if key.is_empty() {
+            problems.push(Problem::error(number, key_column, String::from("missing key before `;`")));
+            continue;
+        }
+        if !REQUIRED.contains(&key) && !OPTIONAL.contains(&key) {
+            problems.push(Problem::error(number, key_column, format!("unknown key `{key}`")));
+            continue;
+        }
+        match seen.iter().find(|(k, _)| k == key) {
+            Some((_, first)) => problems.push(Problem::error(number, key_column, format!("duplicate key `{key}`, first set on line {first}"))),
+            None => seen.push((String::from(key), number)),
+        }
+
+        let rest = &line[semicolon + 1..];
+        let mut offset = semicolon + 1;
+        let mut parts = Vec::new();
+        for part in rest.split(',') {
+            let trimmed = part.trim();
+            if !trimmed.is_empty() {
+                parts.push((column(line, offset + part.find(trimmed).unwrap_or(0)), trimmed));
+            }
+            offset += part.len() + 1;
+        }
+
+        let Some(&(value_column, value)) = parts.first() else {
+            problems.push(Problem::error(number, column(line, semicolon + 1), format!("empty value for `{key}`")));
+            continue;
+        };
+
+        if !rest.trim_end().ends_with(',') {
+            problems.push(Problem::warning(number, column(line, line.trim_end().len()), String::from("missing the trailing comma")));
+        }
+
+        if key == LIST {
+            for (c, status) in parts.iter() {
+                if status.parse::<u16>().is_err() {
+                    problems.push(Problem::error(number, *c, format!("`{status}` is not a status code")));
+                }
+            }
+            continue;
+        }
+
+        if let Some((c, _)) = parts.get(1) {
+            let joined: String = parts.iter().map(|(_, p)| p.replace(' ', "")).collect();
+            problems.push(Problem::error(number, *c, format!("`{key}` takes one value, these parts would be joined into `{joined}`")));
+        }
+        if value.contains(char::is_whitespace) {
+            problems.push(Problem::warning(number, value_column, format!("spaces are dropped, `{key}` is read as `{}`", value.replace(' ', ""))));
+        }
+
+        let url = key == "BaseUrl" || (key == "Sitemap" && value.contains("://"));
+        if let Some(problem) = url.then(|| invalid_url(value)).flatten() {
+            problems.push(Problem::error(number, value_column, problem));
+        }
+        if NUMBERS.contains(&key) && value.parse::<usize>().is_err() {
+            problems.push(Problem::error(number, value_column, format!("`{value}` is not a number")));
+        }
+    }
+
+    for key in REQUIRED {
+        if !provided.contains(&key) && !seen.iter().any(|(k, _)| k == key) {
+            problems.push(Problem::error(0, 0, format!("missing required key `{key}`")));
+        }
+    }
+
+    problems
+}
+
+// the one url check, for config.txt, config.toml and --base-url alike
+pub fn invalid_url(value: &str) -> Option<String> {
+    Url::parse(value).err().map(|_| format!("`{value}` is not a valid url"))
+}
+
+// 1-based column of a byte offset
+fn column(line: &str, offset: usize) -> usize {
+    line[..offset].chars().count() + 1
+}
+
+// config.toml. paths are relative to the base path unless absolute, and
+// unlike config.txt may contain spaces
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Settings {
+    #[serde(default, skip_serializing_if = "PathSettings::is_empty")]
+    pub paths: PathSettings,
+    #[serde(default)]
+    pub http: HttpSettings,
+    #[serde(default, skip_serializing_if = "CrawlSettings::is_empty")]
+    pub crawl: CrawlSettings,
+    #[serde(default, skip_serializing_if = "ScanSettings::is_empty")]
+    pub scan: ScanSettings,
+    pub output: OutputSettings,
+    // sub-sites pursued in one run. each inherits everything above that it
+    // does not set itself
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub sites: Vec<SiteSettings>,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+#[serde(deny_unknown_fields)]
+pub struct PathSettings {
+    // only optional when every site sets its own
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub targets: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sitemap: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+#[serde(deny_unknown_fields)]
+pub struct HttpSettings {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub base_url: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub user_agent: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub concurrency: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rate_limit: Option<usize>,
+    #[serde(default)]
+    pub retry: RetrySettings,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+#[serde(deny_unknown_fields)]
+pub struct RetrySettings {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub attempts: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub delay_ms: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub jitter_ms: Option<usize>,
+    // the longest wait before a retry, Retry-After included
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_delay_ms: Option<usize>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub statuses: Vec<u16>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SiteSettings {
+    pub name: String,
+    pub base_url: String,
+    pub targets: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sitemap: Option<String>,
+    // defaults to a directory named after the site under output.departments
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub departments: Option<String>,
+    // defaults to a directory named after the site under output.files
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub files: Option<String>,
+    // replaces the top level scan section rather than adding to it
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub scan: Option<ScanSettings>,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+#[serde(deny_unknown_fields)]
+pub struct CrawlSettings {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub depth: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pages: Option<usize>,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+#[serde(deny_unknown_fields)]
+pub struct ScanSettings {
+    // a rules file in the config.txt format, read before the rules below
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rules_file: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub content_start: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub content_end: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub rules: Vec<RuleSettings>,
+}
+
+impl PathSettings {
+    fn is_empty(&self) -> bool {
+        self.targets.is_none() && self.sitemap.is_none()
+    }
+}
+
+impl CrawlSettings {
+    fn is_empty(&self) -> bool {
+        self.depth.is_none() && self.pages.is_none()
+    }
+}
+
+impl ScanSettings {
+    fn is_empty(&self) -> bool {
+        self.rules_file.is_none() && self.content_start.is_none() && self.content_end.is_none() && self.rules.is_empty()
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RuleSettings {
+    pub tag: String,
+    pub attribute: String,
+    pub pattern: String,
+    #[serde(default)]
+    pub destination: String,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct OutputSettings {
+    pub departments: String,
+    pub reports: String,
+    pub files: String,
+    // also store each page's content as markdown with front matter
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub markdown: bool,
+}
+
+impl Settings {
+    // urls are checked as in config.txt: the base urls, and a sitemap
+    // given as a url rather than a path
+    pub fn parse(text: &str) -> std::result::Result<Self, String> {
+        let settings: Settings = toml::from_str(text).map_err(|e| e.to_string())?;
+
+        let mut urls: Vec<(String, &str)> = Vec::new();
+        if let Some(url) = &settings.http.base_url {
+            urls.push((String::from("http.base_url"), url));
+        }
+        if let Some(sitemap) = settings.paths.sitemap.as_deref().filter(|s| s.contains("://")) {
+            urls.push((String::from("paths.sitemap"), sitemap));
+        }
+        for site in settings.sites.iter() {
+            urls.push((format!("sites `{}` base_url", site.name), &site.base_url));
+            if let Some(sitemap) = site.sitemap.as_deref().filter(|s| s.contains("://")) {
+                urls.push((format!("sites `{}` sitemap", site.name), sitemap));
+            }
+        }
+
+        let problems: Vec<String> = urls
+            .into_iter()
+            .filter_map(|(key, url)| Some(format!("{key}: {}", invalid_url(url)?)))
+            .collect();
+        match problems.is_empty() {
+            true => Ok(settings),
+            false => Err(problems.join("\n")),
+        }
+    }
+
+    pub fn to_toml(&self) -> std::result::Result<String, String> {
+        toml::to_string_pretty(self).map_err(|e| e.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const VALID: &str = "Departments; d/,\nTargets; t.txt,\nBaseUrl; https://a.edu/as/,\nReports; r/,\nFiles; f/,\n";
+
+    fn described(text: &str) -> Vec<String> {
+        check(text, &[]).iter().map(|p| p.describe("config.txt")).collect()
+    }
+
+    #[test]
+    fn accepts_a_complete_config() {
+        assert!(check(VALID, &[]).is_empty());
+    }
+
+    #[test]
+    fn reports_unknown_and_duplicate_keys_where_they_are() {
+        let text = String::from(VALID) + "Colour; blue,\n  Files; g/,\n";
+        assert_eq!(described(&text), [
+            "config.txt:6:1: error: unknown key `Colour`",
+            "config.txt:7:3: error: duplicate key `Files`, first set on line 5",
+        ]);
+    }
+
+    #[test]
+    fn reports_missing_and_empty_keys() {
+        let text = "Departments; d/,\nTargets; ,\n; x,\nBaseUrl\n";
+        assert_eq!(described(text), [
+            "config.txt:2:9: error: empty value for `Targets`",
+            "config.txt:3:1: error: missing key before `;`",
+            "config.txt:4:1: error: expected `Key; value,`",
+            "config.txt: error: missing required key `BaseUrl`",
+            "config.txt: error: missing required key `Reports`",
+            "config.txt: error: missing required key `Files`",
+        ]);
+    }
+
+    #[test]
+    fn keys_given_on_the_command_line_are_not_missing() {
+        let text = "Departments; d/,\nReports; r/,\nFiles; f/,\n";
+        assert!(check(text, &["Targets", "BaseUrl"]).is_empty());
+    }
+
+    #[test]
+    fn reports_malformed_urls_and_numbers() {
+        let text = String::from(VALID).replace("https://a.edu/as/", "a.edu/as/") + "Sitemap; http://[bad,\nConcurrency; four,\n";
+        assert_eq!(described(&text), [
+            "config.txt:3:10: error: `a.edu/as/` is not a valid url",
+            "config.txt:6:10: error: `http://[bad` is not a valid url",
+            "config.txt:7:14: error: `four` is not a number",
+        ]);
+    }
+
+    #[test]
+    fn the_trailing_comma_and_spaces_are_only_warnings() {
+        let text = String::from(VALID) + "Rules; my rules.txt\n";
+        let problems = check(&text, &[]);
+        assert!(problems.iter().all(|p| p.warning));
+        assert_eq!(described(&text), [
+            "config.txt:6:20: warning: missing the trailing comma",
+            "config.txt:6:8: warning: spaces are dropped, `Rules` is read as `myrules.txt`",
+        ]);
+    }
+
+    #[test]
+    fn reports_values_that_would_be_joined() {
+        let text = String::from(VALID) + "UserAgent; a, b,\nRetryStatuses; 429, 5xx,\n";
+        assert_eq!(described(&text), [
+            "config.txt:6:15: error: `UserAgent` takes one value, these parts would be joined into `ab`",
+            "config.txt:7:21: error: `5xx` is not a status code",
+        ]);
+    }
+
+    #[test]
+    fn reports_every_problem_in_one_pass() {
+        let text = "Departments; d/,\nDepartments; e/,\nBaseUrl; nope,\nOops; 1,\nRateLimit; fast\n";
+        let problems = check(text, &[]);
+        let lines: Vec<usize> = problems.iter().map(|p| p.line).collect();
+        assert_eq!(lines, [2, 3, 4, 5, 5, 0, 0, 0]);
+        assert_eq!(problems.iter().filter(|p| !p.warning).count(), 7);
+    }
+
+    #[test]
+    fn checks_toml_urls_like_the_legacy_format() {
+        let output = "[output]\ndepartments = \"d\"\nreports = \"r\"\nfiles = \"f\"\n";
+        let toml = String::from("[paths]\nsitemap = \"sitemap.xml\"\n[http]\nbase_url = \"https://a.edu/as/\"\n") + output;
+        assert!(Settings::parse(&toml).is_ok());
+
+        let toml = String::from("[paths]\nsitemap = \"http://[bad\"\n[http]\nbase_url = \"a.edu/as/\"\n") + output
+            + "[[sites]]\nname = \"b\"\nbase_url = \"b.edu\"\ntargets = \"t.txt\"\n";
+        assert_eq!(Settings::parse(&toml).err().as_deref(), Some("\
+            http.base_url: `a.edu/as/` is not a valid url\n\
+            paths.sitemap: `http://[bad` is not a valid url\n\
+            sites `b` base_url: `b.edu` is not a valid url"));
+    }
+}
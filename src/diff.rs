@@ -0,0 +1,144 @@
+// line diffs between two captures of a page's content region
+
+// past this many cells the table of the lines left between the common start
+// and end gets too large, about 4MB, so they are reported as replaced instead
+const MAX_CELLS: usize = 1_000_000;
+
+pub enum Change<'a> {
+    Same(&'a str),
+    Removed(&'a str),
+    Added(&'a str),
+}
+
+pub fn diff_lines<'a>(a: &[&'a str], b: &[&'a str]) -> Vec<Change<'a>> {
+    // edits are usually local, so lines shared at the start and end are
+    // matched up front and kept out of the table
+    let start = a.iter().zip(b.iter()).take_while(|(x, y)| x == y).count();
+    let end = a[start..].iter().rev().zip(b[start..].iter().rev()).take_while(|(x, y)| x == y).count();
+    let (middle_a, middle_b) = (&a[start..a.len() - end], &b[start..b.len() - end]);
+
+    let mut changes: Vec<Change> = a[..start].iter().map(|l| Change::Same(l)).collect();
+    changes.extend(diff_middle(middle_a, middle_b));
+    changes.extend(a[a.len() - end..].iter().map(|l| Change::Same(l)));
+    changes
+}
+
+fn diff_middle<'a>(a: &[&'a str], b: &[&'a str]) -> Vec<Change<'a>> {
+    if a.len().saturating_mul(b.len()) > MAX_CELLS {
+        return a.iter().map(|l| Change::Removed(l))
+            .chain(b.iter().map(|l| Change::Added(l)))
+            .collect();
+    }
+
+    // lcs[i][j] is the longest common subsequence of a[i..] and b[j..]
+    let mut lcs = vec![vec![0u32; b.len() + 1]; a.len() + 1];
+    for i in (0..a.len()).rev() {
+        for j in (0..b.len()).rev() {
+            lcs[i][j] = if a[i] == b[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+
+    let (mut i, mut j) = (0, 0);
+    let mut changes = Vec::new();
+    while i < a.len() || j < b.len() {
+        if i < a.len() && j < b.len() && a[i] == b[j] {
+            changes.push(Change::Same(a[i]));
+            i += 1;
+            j += 1;
+        } else if i < a.len() && (j == b.len() || lcs[i + 1][j] >= lcs[i][j + 1]) {
+            changes.push(Change::Removed(a[i]));
+            i += 1;
+        } else {
+            changes.push(Change::Added(b[j]));
+            j += 1;
+        }
+    }
+    changes
+}
+
+// changed lines with `context` unchanged lines around them, hunks separated by `...`
+pub fn render(changes: &[Change], context: usize) -> String {
+    let near_change = |at: usize| {
+        let start = at.saturating_sub(context);
+        let end = (at + context + 1).min(changes.len());
+        changes[start..end].iter().any(|c| !matches!(c, Change::Same(_)))
+    };
+
+    let mut out = String::new();
+    let mut skipped = false;
+    for (at, change) in changes.iter().enumerate() {
+        if !near_change(at) {
+            skipped = true;
+            continue;
+        }
+        if skipped && !out.is_empty() {
+            out += "...\n";
+        }
+        skipped = false;
+
+        out += &match change {
+            Change::Same(l) => String::from("  ") + l,
+            Change::Removed(l) => String::from("- ") + l,
+            Change::Added(l) => String::from("+ ") + l,
+        };
+        out += "\n";
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lines(text: &str) -> Vec<&str> {
+        text.split_whitespace().collect()
+    }
+
+    fn diff(a: &str, b: &str) -> String {
+        render(&diff_lines(&lines(a), &lines(b)), 10)
+    }
+
+    #[test]
+    fn marks_removed_and_added_lines() {
+        assert_eq!(diff("a b c", "a b c"), "");
+        assert_eq!(diff("a b c", "a x c"), "  a\n- b\n+ x\n  c\n");
+        assert_eq!(diff("a b c d", "b c e"), "- a\n  b\n  c\n- d\n+ e\n");
+        assert_eq!(diff("", "a"), "+ a\n");
+        assert_eq!(diff("a", ""), "- a\n");
+    }
+
+    #[test]
+    fn keeps_the_longest_common_run() {
+        assert_eq!(diff("x a b c y", "a b c"), "- x\n  a\n  b\n  c\n- y\n");
+        assert_eq!(diff("a b a b", "b a b a"), "- a\n  b\n  a\n  b\n+ a\n");
+    }
+
+    #[test]
+    fn shows_context_around_changes_only() {
+        let a = lines("1 2 3 4 5 6 7 8 9");
+        let b = lines("1 2 3 4 x 6 7 8 9");
+        assert_eq!(render(&diff_lines(&a, &b), 1), "  4\n- 5\n+ x\n  6\n");
+
+        let b = lines("0 2 3 4 5 6 7 8 0");
+        assert_eq!(render(&diff_lines(&a, &b), 1), "- 1\n+ 0\n  2\n...\n  8\n- 9\n+ 0\n");
+    }
+
+    #[test]
+    fn replaces_what_is_too_large_to_compare_but_keeps_the_common_ends() {
+        let a: Vec<String> = (0..2000).map(|i| format!("a{i}")).collect();
+        let b: Vec<String> = (0..2000).map(|i| format!("b{i}")).collect();
+        let a: Vec<&str> = ["top"].into_iter().chain(a.iter().map(String::as_str)).chain(["end"]).collect();
+        let b: Vec<&str> = ["top"].into_iter().chain(b.iter().map(String::as_str)).chain(["end"]).collect();
+
+        let changes = diff_lines(&a, &b);
+        assert_eq!(changes.len(), 4002);
+        assert!(matches!(changes[0], Change::Same("top")));
+        assert!(matches!(changes[1], Change::Removed("a0")));
+        assert!(matches!(changes[2001], Change::Added("b0")));
+        assert!(matches!(changes[4001], Change::Same("end")));
+    }
+}
@@ -0,0 +1,643 @@
+use std::collections::HashSet;
+use std::ops::Range;
+
+// elements whose content is never parsed as markup
+const RAW_TEXT: [&str; 4] = ["script", "style", "textarea", "title"];
+
+// elements that never have an end tag
+const VOID: [&str; 13] = ["area", "base", "br", "col", "embed", "hr", "img", "input", "link", "meta", "source", "track", "wbr"];
+
+// elements whose end tag is implied by a sibling of the same kind
+const IMPLIED_END: [&str; 8] = ["li", "p", "dt", "dd", "tr", "td", "th", "option"];
+
+// page chrome, dropped from a clean fragment along with everything inside it
+const CHROME: [&str; 7] = ["header", "footer", "nav", "script", "style", "noscript", "template"];
+
+pub struct Attribute {
+    pub name: String,
+    pub value: String,
+}
+
+pub struct Tag {
+    pub name: String,
+    pub attributes: Vec<Attribute>,
+    pub self_closing: bool,
+}
+
+impl Tag {
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.attributes
+            .iter()
+            .find(|a| a.name == name)
+            .map(|a| a.value.as_str())
+    }
+}
+
+pub enum Token {
+    Start(Tag),
+    End(String),
+    Text(String),
+    Comment(String),
+    Declaration(String),
+}
+
+pub struct Tokenizer<'a> {
+    data: &'a [u8],
+    pos: usize,
+    raw: Option<String>,
+}
+
+impl<'a> Tokenizer<'a> {
+    pub fn new(data: &'a [u8]) -> Self {
+        Self {
+            data,
+            pos: 0,
+            raw: None,
+        }
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.data.get(self.pos).copied()
+    }
+
+    fn starts_with(&self, s: &str) -> bool {
+        self.data[self.pos..].starts_with(s.as_bytes())
+    }
+
+    fn starts_with_ignore_case(&self, s: &str) -> bool {
+        self.data.len() - self.pos >= s.len()
+            && self.data[self.pos..self.pos + s.len()].eq_ignore_ascii_case(s.as_bytes())
+    }
+
+    fn index_of(&self, s: &str) -> Option<usize> {
+        self.data[self.pos..]
+            .windows(s.len())
+            .position(|w| w == s.as_bytes())
+            .map(|i| self.pos + i)
+    }
+
+    fn text(&self, range: Range<usize>) -> String {
+        String::from_utf8_lossy(&self.data[range]).into_owned()
+    }
+
+    fn skip_whitespace(&mut self) {
+        while let Some(b) = self.peek() {
+            if !b.is_ascii_whitespace() {
+                break;
+            }
+            self.pos += 1;
+        }
+    }
+
+    fn take_until<F: Fn(u8) -> bool>(&mut self, f: F) -> Range<usize> {
+        let start = self.pos;
+        while let Some(b) = self.peek() {
+            if f(b) {
+                break;
+            }
+            self.pos += 1;
+        }
+        start..self.pos
+    }
+
+    // consumes up to and including `end`, or to the end of input
+    fn take_through(&mut self, end: &str) -> Range<usize> {
+        let start = self.pos;
+        match self.index_of(end) {
+            Some(i) => {
+                self.pos = i + end.len();
+                start..i
+            },
+            None => {
+                self.pos = self.data.len();
+                start..self.pos
+            },
+        }
+    }
+
+    fn raw_text(&mut self, name: String) -> Token {
+        let start = self.pos;
+        let close = String::from("</") + &name;
+        while self.pos < self.data.len() && !self.starts_with_ignore_case(&close) {
+            self.pos += 1;
+        }
+        Token::Text(self.text(start..self.pos))
+    }
+
+    fn attribute(&mut self) -> Option<Attribute> {
+        let name = self.take_until(|b| b.is_ascii_whitespace() || b == b'=' || b == b'>' || b == b'/');
+        if name.is_empty() {
+            return None;
+        }
+        let name = self.text(name).to_ascii_lowercase();
+
+        self.skip_whitespace();
+        if self.peek() != Some(b'=') {
+            return Some(Attribute { name, value: String::new() });
+        }
+        self.pos += 1;
+        self.skip_whitespace();
+
+        let value = match self.peek() {
+            Some(q) if q == b'"' || q == b'\'' => {
+                self.pos += 1;
+                let value = self.take_until(|b| b == q);
+                self.pos = (self.pos + 1).min(self.data.len());
+                value
+            },
+            _ => self.take_until(|b| b.is_ascii_whitespace() || b == b'>'),
+        };
+
+        Some(Attribute { name, value: decode_entities(&self.text(value)) })
+    }
+
+    fn start_tag(&mut self) -> Token {
+        let name = self.take_until(|b| b.is_ascii_whitespace() || b == b'>' || b == b'/');
+        let name = self.text(name).to_ascii_lowercase();
+        let mut attributes = Vec::new();
+        let mut self_closing = false;
+
+        loop {
+            self.skip_whitespace();
+            match self.peek() {
+                None => break,
+                Some(b'>') => {
+                    self.pos += 1;
+                    break;
+                },
+                Some(b'/') => {
+                    self.pos += 1;
+                    self_closing = self.peek() == Some(b'>');
+                },
+                Some(_) => match self.attribute() {
+                    Some(a) => attributes.push(a),
+                    None => self.pos += 1,
+                },
+            }
+        }
+
+        if !self_closing && RAW_TEXT.contains(&name.as_str()) {
+            self.raw = Some(name.clone());
+        }
+
+        Token::Start(Tag { name, attributes, self_closing })
+    }
+
+    fn end_tag(&mut self) -> Token {
+        let name = self.take_until(|b| b.is_ascii_whitespace() || b == b'>' || b == b'/');
+        let name = self.text(name).to_ascii_lowercase();
+        self.take_through(">");
+        Token::End(name)
+    }
+
+    fn next_token(&mut self) -> Option<Token> {
+        if let Some(name) = self.raw.take() {
+            if self.pos < self.data.len() && !self.starts_with_ignore_case(&(String::from("</") + &name)) {
+                return Some(self.raw_text(name));
+            }
+        }
+
+        match self.peek()? {
+            b'<' if self.starts_with("<!--") => {
+                self.pos += 4;
+                let comment = self.take_through("-->");
+                Some(Token::Comment(self.text(comment)))
+            },
+            b'<' if self.starts_with("<![CDATA[") => {
+                self.pos += 9;
+                let data = self.take_through("]]>");
+                Some(Token::Text(self.text(data)))
+            },
+            b'<' if self.starts_with("<!") || self.starts_with("<?") => {
+                self.pos += 2;
+                let declaration = self.take_through(">");
+                Some(Token::Declaration(self.text(declaration)))
+            },
+            b'<' if self.starts_with("</") => {
+                self.pos += 2;
+                Some(self.end_tag())
+            },
+            b'<' if self.data.get(self.pos + 1).is_some_and(|b| b.is_ascii_alphabetic()) => {
+                self.pos += 1;
+                Some(self.start_tag())
+            },
+            _ => {
+                let start = self.pos;
+                self.pos += 1;
+                self.take_until(|b| b == b'<');
+                Some(Token::Text(self.text(start..self.pos)))
+            },
+        }
+    }
+}
+
+impl<'a> Iterator for Tokenizer<'a> {
+    // the token and the bytes of input it was read from
+    type Item = (Range<usize>, Token);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let start = self.pos;
+        self.next_token().map(|t| (start..self.pos, t))
+    }
+}
+
+// a tag name like `main`, or a single attribute like `id="content"`
+#[derive(Clone)]
+pub enum Selector {
+    Tag(String),
+    Attribute(String, String),
+}
+
+impl Selector {
+    pub fn parse(selector: &str) -> Self {
+        let selector = selector.trim();
+        if !selector.contains('=') {
+            return Selector::Tag(selector.trim_start_matches('<').to_ascii_lowercase());
+        }
+
+        let wrapped = String::from("<x ") + selector + ">";
+        match Tokenizer::new(wrapped.as_bytes()).next() {
+            Some((_, Token::Start(mut tag))) if !tag.attributes.is_empty() => {
+                let a = tag.attributes.remove(0);
+                Selector::Attribute(a.name, a.value)
+            },
+            _ => Selector::Tag(String::new()),
+        }
+    }
+
+    pub fn matches(&self, tag: &Tag) -> bool {
+        match self {
+            Selector::Tag(name) => &tag.name == name,
+            Selector::Attribute(name, value) => match tag.get(name) {
+                Some(v) if name == "class" => v.split_ascii_whitespace().any(|c| c == value),
+                Some(v) => v == value,
+                None => false,
+            },
+        }
+    }
+}
+
+// content lives after the tag matching `start` and before the tag matching `end`
+pub struct Region {
+    pub start: Selector,
+    pub end: Selector,
+}
+
+impl Region {
+    pub fn new(start: &str, end: &str) -> Self {
+        Self {
+            start: Selector::parse(start),
+            end: Selector::parse(end),
+        }
+    }
+}
+
+// tokens that fall within the region, or every token when there is none
+pub fn region_tokens<'a>(data: &'a [u8], region: Option<&'a Region>) -> impl Iterator<Item = (Range<usize>, Token)> + 'a {
+    let mut inside = region.is_none();
+    Tokenizer::new(data)
+        .skip_while(move |(_, token)| match (region, token) {
+            (Some(r), Token::Start(tag)) if !inside && r.start.matches(tag) => {
+                inside = true;
+                true
+            },
+            _ => !inside,
+        })
+        .take_while(move |(_, token)| match (region, token) {
+            (Some(r), Token::Start(tag)) => !r.end.matches(tag),
+            _ => true,
+        })
+}
+
+// the markup inside the region, or the whole document when there is none
+pub fn region_html(data: &[u8], region: Option<&Region>) -> String {
+    region_tokens(data, region)
+        .map(|(range, _)| String::from_utf8_lossy(&data[range]).into_owned())
+        .collect()
+}
+
+// the region with page chrome, comments, styling and wrapper divs removed,
+// ready to paste into another site. tags left open are closed at the end
+pub fn clean_fragment(data: &[u8], region: Option<&Region>) -> String {
+    let mut out = String::new();
+    let mut open: Vec<String> = Vec::new();
+    let mut dropping: Option<(String, usize)> = None;
+
+    for (range, token) in region_tokens(data, region) {
+        if let Some((name, depth)) = &mut dropping {
+            match &token {
+                Token::Start(t) if &t.name == name && !t.self_closing => *depth += 1,
+                Token::End(n) if n == name => {
+                    *depth -= 1;
+                    if *depth == 0 {
+                        dropping = None;
+                    }
+                },
+                _ => (),
+            }
+            continue;
+        }
+
+        match token {
+            Token::Start(t) if CHROME.contains(&t.name.as_str()) => {
+                if !t.self_closing {
+                    dropping = Some((t.name, 1));
+                }
+            },
+            Token::Start(t) if t.name == "div" => (),
+            Token::Start(t) => {
+                if IMPLIED_END.contains(&t.name.as_str()) && open.last() == Some(&t.name) {
+                    out = out + "</" + &t.name + ">";
+                    open.pop();
+                }
+                out += &open_tag(&t);
+                if !t.self_closing && !VOID.contains(&t.name.as_str()) {
+                    open.push(t.name);
+                }
+            },
+            // an end tag closes any elements left open inside it
+            Token::End(n) => if let Some(at) = open.iter().rposition(|o| o == &n) {
+                for name in open.drain(at..).rev() {
+                    out = out + "</" + &name + ">";
+                }
+            },
+            Token::Text(_) => out += &String::from_utf8_lossy(&data[range]),
+            Token::Comment(_) | Token::Declaration(_) => (),
+        }
+    }
+    for name in open.into_iter().rev() {
+        out = out + "</" + &name + ">";
+    }
+
+    out.lines()
+        .map(str::trim_end)
+        .filter(|l| !l.is_empty())
+        .fold(String::new(), |acc, l| acc + l + "\n")
+}
+
+fn open_tag(tag: &Tag) -> String {
+    let kept = tag.attributes
+        .iter()
+        .filter(|a| a.name != "style" && a.name != "class" && !a.name.starts_with("data-"))
+        .filter(|a| !runs_script(a));
+
+    let mut out = String::from("<") + &tag.name;
+    for a in kept {
+        out = out + " " + &a.name + "=\"" + &a.value.replace('&', "&amp;").replace('"', "&quot;") + "\"";
+    }
+    out + if tag.self_closing { " />" } else { ">" }
+}
+
+// event handlers, and urls that run script when followed. browsers ignore
+// whitespace and control characters inside the scheme
+fn runs_script(attribute: &Attribute) -> bool {
+    let value: String = attribute.value
+        .chars()
+        .filter(|c| !c.is_ascii_whitespace() && !c.is_control())
+        .take(11)
+        .collect();
+    attribute.name.starts_with("on") || value.eq_ignore_ascii_case("javascript:")
+}
+
+// the fragment with every href, src and srcset candidate passed through
+// `rewrite`. tags with nothing rewritten are kept as they were
+pub fn rewrite_links(fragment: &str, mut rewrite: impl FnMut(&str) -> Option<String>) -> String {
+    let data = fragment.as_bytes();
+    let mut out = String::new();
+
+    for (range, token) in Tokenizer::new(data) {
+        let Token::Start(mut tag) = token else {
+            out += &String::from_utf8_lossy(&data[range]);
+            continue;
+        };
+
+        let mut changed = false;
+        for a in tag.attributes.iter_mut() {
+            let value = match a.name.as_str() {
+                "href" | "src" => rewrite(&a.value),
+                "srcset" => {
+                    let mut any = false;
+                    let candidates: Vec<String> = a.value
+                        .split(',')
+                        .map(|candidate| {
+                            let candidate = candidate.trim();
+                            let (url, descriptor) = candidate.split_once(char::is_whitespace).unwrap_or((candidate, ""));
+                            match rewrite(url) {
+                                Some(url) => {
+                                    any = true;
+                                    String::from((url + " " + descriptor.trim()).trim_end())
+                                },
+                                None => String::from(candidate),
+                            }
+                        })
+                        .collect();
+                    any.then(|| candidates.join(", "))
+                },
+                _ => None,
+            };
+            if let Some(value) = value {
+                a.value = value;
+                changed = true;
+            }
+        }
+
+        match changed {
+            true => out += &open_tag(&tag),
+            false => out += &String::from_utf8_lossy(&data[range]),
+        }
+    }
+    out
+}
+
+// the text of the document's `<title>`
+pub fn page_title(data: &[u8]) -> Option<String> {
+    let mut tokens = Tokenizer::new(data).map(|(_, token)| token);
+    tokens.find(|token| matches!(token, Token::Start(tag) if tag.name == "title"))?;
+    match tokens.next()? {
+        Token::Text(text) => Some(decode_entities(text.trim())).filter(|t| !t.is_empty()),
+        _ => None,
+    }
+}
+
+// every value of `attribute` on `tag` elements that contains `pattern`.
+// srcset values are split into their candidate urls
+pub fn extract_attributes(data: &[u8], region: Option<&Region>, tag: &str, attribute: &str, pattern: &str) -> HashSet<String> {
+    let (tag, attribute) = (tag.to_ascii_lowercase(), attribute.to_ascii_lowercase());
+    region_tokens(data, region)
+        .filter_map(|(_, token)| match token {
+            Token::Start(t) if t.name == tag => t.get(&attribute).map(String::from),
+            _ => None,
+        })
+        .flat_map(|value| match attribute.as_str() {
+            "srcset" => value
+                .split(',')
+                .filter_map(|candidate| candidate.split_ascii_whitespace().next())
+                .map(String::from)
+                .collect(),
+            _ => vec![value],
+        })
+        .filter(|value| value.contains(pattern))
+        .collect()
+}
+
+pub fn decode_entities(s: &str) -> String {
+    if !s.contains('&') {
+        return String::from(s);
+    }
+
+    let mut out = String::new();
+    let mut rest = s;
+    while let Some(i) = rest.find('&') {
+        out += &rest[..i];
+        rest = &rest[i..];
+        let decoded = rest.find(';').filter(|&end| end <= 10).and_then(|end| {
+            let c = match &rest[1..end] {
+                "amp" => Some('&'),
+                "lt" => Some('<'),
+                "gt" => Some('>'),
+                "quot" => Some('"'),
+                "apos" => Some('\''),
+                "nbsp" => Some('\u{a0}'),
+                e if e.starts_with("#x") || e.starts_with("#X") => u32::from_str_radix(&e[2..], 16).ok().and_then(char::from_u32),
+                e if e.starts_with('#') => e[1..].parse().ok().and_then(char::from_u32),
+                _ => None,
+            };
+            c.map(|c| (c, end))
+        });
+        match decoded {
+            Some((c, end)) => {
+                out.push(c);
+                rest = &rest[end + 1..];
+            },
+            None => {
+                out.push('&');
+                rest = &rest[1..];
+            },
+        }
+    }
+    out + rest
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hrefs(html: &str, region: Option<&Region>) -> Vec<String> {
+        let mut found: Vec<String> = extract_attributes(html.as_bytes(), region, "a", "href", "")
+            .into_iter()
+            .collect();
+        found.sort();
+        found
+    }
+
+    fn csun() -> Region {
+        Region::new("id=\"content\"", "class=\"layout-csun--footer\"")
+    }
+
+    #[test]
+    fn cleans_the_content_region() {
+        let html = r#"<nav>menu</nav><div id="content"><header><h1>Site</h1></header>
+            <div class="field field--name-body"><div class="clearfix">
+            <p style="color:red" class="lead" data-x="1">Tom &amp; Jerry</p>
+            <!-- edited --><script>alert(1)</script><style>p{}</style>
+            <img src="/a.png" alt='say "hi"' onerror="alert(1)"><br/>
+            <a href="javascript:alert(1)" onClick="go()">run</a> <a href=" JAVA&#9;SCRIPT:x">tab</a> <a href="/javascript:ok">ok</a>
+            <details open><summary>more</summary></details>
+            </div></div><footer>links</footer></div><div class="layout-csun--footer">csun</div>"#;
+        let fragment = clean_fragment(html.as_bytes(), Some(&csun()));
+        assert_eq!(fragment.lines().map(str::trim).collect::<Vec<_>>(), [
+            "<p>Tom &amp; Jerry</p>",
+            r#"<img src="/a.png" alt="say &quot;hi&quot;"><br />"#,
+            r#"<a>run</a> <a>tab</a> <a href="/javascript:ok">ok</a>"#,
+            r#"<details open=""><summary>more</summary></details>"#,
+        ]);
+    }
+
+    #[test]
+    fn closes_what_the_region_leaves_open() {
+        let html = r#"<main id="content"><section><ul><li>one<li>two</ul><p>cut"#;
+        let fragment = clean_fragment(html.as_bytes(), Some(&csun()));
+        assert_eq!(fragment, "<section><ul><li>one</li><li>two</li></ul><p>cut</p></section>\n");
+    }
+
+    #[test]
+    fn rewrites_only_the_links_asked_for() {
+        let html = r#"<p><a href="/old">o</a> <a href='https://elsewhere.org/'>e</a>
+<img alt="x" src="/f/a.png"><source srcset="/f/a.png 2x, /f/b.png"></p>"#;
+        let rewritten = rewrite_links(html, |link| match link {
+            "/old" => Some(String::from("/new")),
+            "/f/a.png" => Some(String::from("/files/a.png")),
+            _ => None,
+        });
+        assert_eq!(rewritten, r#"<p><a href="/new">o</a> <a href='https://elsewhere.org/'>e</a>
+<img alt="x" src="/files/a.png"><source srcset="/files/a.png 2x, /f/b.png"></p>"#);
+    }
+
+    #[test]
+    fn reads_quoted_unquoted_and_spaced_values() {
+        let html = r#"<a href="/one">1</a><a href='/two'>2</a><a href=/three>3</a><a href = "/four" >4</a>"#;
+        assert_eq!(hrefs(html, None), ["/four", "/one", "/three", "/two"]);
+    }
+
+    #[test]
+    fn finds_attribute_after_others_and_after_tags_without_it() {
+        let html = r#"<img width=300 src="/a.png"><a name="top">x</a><a class="btn" HREF="/b.pdf">b</a>"#;
+        assert_eq!(hrefs(html, None), ["/b.pdf"]);
+        let src = extract_attributes(html.as_bytes(), None, "IMG", "src", "");
+        assert!(src.contains("/a.png"));
+    }
+
+    #[test]
+    fn does_not_match_tags_sharing_a_prefix() {
+        let html = r#"<abbr href="/no">x</abbr><article href="/no"></article><a href="/yes">y</a>"#;
+        assert_eq!(hrefs(html, None), ["/yes"]);
+    }
+
+    #[test]
+    fn ignores_comments_and_script_content() {
+        let html = r#"<!-- <a href="/commented"> --><script>var s = '<a href="/scripted">';</script><a href="/real">r</a>"#;
+        assert_eq!(hrefs(html, None), ["/real"]);
+    }
+
+    #[test]
+    fn decodes_entities_in_values() {
+        let html = r#"<a href="/file?a=1&amp;b=2">x</a><a href="/caf&#233;">y</a>"#;
+        assert_eq!(hrefs(html, None), ["/caf\u{e9}", "/file?a=1&b=2"]);
+    }
+
+    #[test]
+    fn limits_to_content_region() {
+        let html = r#"<a href="/nav">n</a><div class="wrap" id="content"><a href="/inside">i</a></div>
+            <footer class="region layout-csun--footer"><a href="/footer">f</a></footer>"#;
+        assert_eq!(hrefs(html, Some(&csun())), ["/inside"]);
+    }
+
+    #[test]
+    fn missing_region_start_finds_nothing() {
+        let html = r#"<a href="/anywhere">a</a>"#;
+        assert!(hrefs(html, Some(&csun())).is_empty());
+    }
+
+    #[test]
+    fn filters_by_pattern() {
+        let html = r#"<a href="/sites/default/files/a.pdf">a</a><a href="/about">b</a>"#;
+        let found = extract_attributes(html.as_bytes(), None, "a", "href", "/sites/default/files/");
+        assert_eq!(found.len(), 1);
+        assert!(found.contains("/sites/default/files/a.pdf"));
+    }
+
+    #[test]
+    fn splits_srcset_candidates() {
+        let html = r#"<picture><source srcset="/img/a.jpg 480w, /img/b.jpg 2x"><img src="/img/c.jpg"></picture>"#;
+        let found = extract_attributes(html.as_bytes(), None, "source", "srcset", "/img/");
+        assert_eq!(found.len(), 2);
+        assert!(found.contains("/img/a.jpg") && found.contains("/img/b.jpg"));
+    }
+
+    #[test]
+    fn survives_truncated_markup() {
+        let html = r#"<a href="/ok">ok</a><a href="/cut"#;
+        assert_eq!(hrefs(html, None), ["/cut", "/ok"]);
+        assert_eq!(hrefs("<a href=", None), [""]);
+    }
+}
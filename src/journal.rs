@@ -0,0 +1,130 @@
+use std::collections::BTreeMap;
+use std::fs::{create_dir_all, read_to_string, File, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::Path;
+
+use crate::record::Record;
+use crate::Result;
+
+// one json record per line, appended as each target completes so an
+// interrupted run can be resumed
+pub struct Journal {
+    file: File,
+}
+
+impl Journal {
+    fn location(reports: &str, run_id: &str) -> String {
+        String::from(reports) + "journal/" + run_id + ".jsonl"
+    }
+
+    pub fn open(reports: &str, run_id: &str) -> Result<Self> {
+        let dir = String::from(reports) + "journal/";
+        if !Path::new(&dir).is_dir() {
+            create_dir_all(&dir)?;
+        }
+
+        let mut file = OpenOptions::new()
+            .create(true)
+            .read(true)
+            .append(true)
+            .open(Journal::location(reports, run_id))?;
+
+        // a line cut short by a crash is ended, so the next record starts a line of its own
+        if file.metadata()?.len() > 0 {
+            let mut last = [0; 1];
+            file.seek(SeekFrom::End(-1))?;
+            file.read_exact(&mut last)?;
+            if last != *b"\n" {
+                file.write_all(b"\n")?;
+            }
+        }
+        Ok(Self { file })
+    }
+
+    // records from an earlier run. a line that is not one, such as one cut
+    // short by a crash, is skipped with a warning
+    pub fn read(reports: &str, run_id: &str) -> Result<Vec<Record>> {
+        let location = Journal::location(reports, run_id);
+        let text = read_to_string(&location)
+            .map_err(|e| format!("Cannot resume run {run_id} from {location}: {e}"))?;
+
+        Ok(text
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| !line.trim().is_empty())
+            .filter_map(|(at, line)| match serde_json::from_str(line) {
+                Ok(record) => Some(record),
+                Err(e) => {
+                    println!("{location}:{}: skipped, not a record: {e}", at + 1);
+                    None
+                },
+            })
+            .collect())
+    }
+
+    // the last record for each site and target, so a target retried by an
+    // earlier resume is only reported once
+    pub fn latest(reports: &str, run_id: &str) -> Result<Vec<Record>> {
+        let mut latest = BTreeMap::new();
+        for record in Journal::read(reports, run_id)? {
+            latest.insert((record.site.clone(), record.target.clone()), record);
+        }
+        Ok(latest.into_values().collect())
+    }
+
+    pub fn append(&mut self, record: &Record) -> Result<()> {
+        let line = serde_json::to_string(record)? + "\n";
+        self.file.write_all(line.as_bytes())?;
+        self.file.flush()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::{remove_dir_all, write};
+
+    fn reports(name: &str) -> String {
+        let dir = std::env::temp_dir().join(format!("web_migration-{}-{name}/", std::process::id()));
+        let _ = remove_dir_all(&dir);
+        String::from(dir.to_str().unwrap())
+    }
+
+    #[test]
+    fn resumes_after_a_line_cut_short() {
+        let reports = reports("journal-cut");
+        let mut journal = Journal::open(&reports, "run").unwrap();
+        journal.append(&Record::new("", "dept/", "https://a.edu/dept/")).unwrap();
+        drop(journal);
+
+        let location = Journal::location(&reports, "run");
+        let text = read_to_string(&location).unwrap() + "{\"site\":\"\",\"tar";
+        write(&location, text).unwrap();
+
+        let mut journal = Journal::open(&reports, "run").unwrap();
+        journal.append(&Record::new("", "other/", "https://a.edu/other/")).unwrap();
+
+        let targets: Vec<String> = Journal::read(&reports, "run").unwrap().into_iter().map(|r| r.target).collect();
+        assert_eq!(targets, ["dept/", "other/"]);
+        remove_dir_all(&reports).unwrap();
+    }
+
+    #[test]
+    fn keeps_the_last_record_of_a_target_and_its_entry() {
+        let reports = reports("journal-latest");
+        let mut journal = Journal::open(&reports, "run").unwrap();
+        let mut failed = Record::new("", "dept/", "https://a.edu/dept/");
+        failed.error = Some(String::from("timed out"));
+        failed.entry = Some(crate::listing::Entry { path: String::from("dept/"), priority: Some(3), ..Default::default() });
+        journal.append(&failed).unwrap();
+        journal.append(&Record::new("", "dept/", "https://a.edu/dept/")).unwrap();
+        journal.append(&failed).unwrap();
+
+        let latest = Journal::latest(&reports, "run").unwrap();
+        assert_eq!(latest.len(), 1);
+        assert_eq!(latest[0].error.as_deref(), Some("timed out"));
+        assert_eq!(latest[0].entry.as_ref().and_then(|e| e.priority), Some(3));
+        remove_dir_all(&reports).unwrap();
+    }
+}
@@ -1,23 +1,60 @@
-use std::fs::{File, create_dir};
-use chrono::Utc;
+use std::fs::{File, create_dir, create_dir_all, read_dir, read_to_string};
+use chrono::{TimeZone, Utc};
 use std::io::{BufReader, BufWriter, Write, BufRead};
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use bytes::Bytes;
-use reqwest::{Client, Method, RequestBuilder};
-use std::time::Duration;
+use reqwest::{Client, Method, RequestBuilder, StatusCode, Url};
+use reqwest::header::{HeaderMap, HeaderValue, CONTENT_TYPE, ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED};
+use std::time::{Duration, Instant};
 use error_chain::error_chain;
 use tokio::runtime::{Runtime, Builder};
 // use image::io::Reader as ImageReader;
-use std::collections::HashSet;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::sync::Mutex;
+
+pub mod html;
+mod sitemap;
+mod robots;
+mod retry;
+mod record;
+mod throttle;
+mod diff;
+mod journal;
+mod config;
+mod listing;
+mod markdown;
+mod redirects;
+mod links;
+
+use html::{Region, Selector};
+use robots::Robots;
+use retry::{Attempt, RetryPolicy};
+use record::{Failure, LinkKind, Record, Rewrite, SavedFile};
+use redirects::Redirect;
+use links::{Links, Source};
+use journal::Journal;
+use config::{RuleSettings, Settings};
+use throttle::Throttle;
+use futures::stream::{self, StreamExt};
 
 error_chain! {
     foreign_links {
         Reqwest(reqwest::Error);
         Io(std::io::Error);
         Tokio(tokio::task::JoinError);
+        Url(url::ParseError);
+        Json(serde_json::Error);
+    }
+
+    errors {
+        Config(message: String) {
+            description("invalid configuration")
+            display("{}", message)
+        }
     }
 }
 
+#[derive(Clone)]
 enum Daily {
     Date(String),
     Time(i64),
@@ -32,6 +69,7 @@ impl Daily {
     }
 }
 
+#[derive(Clone)]
 struct Today {
     date: Daily,
     time: Daily,
@@ -47,11 +85,25 @@ impl Today {
     }
 }
 
+#[derive(Clone)]
 enum Paths {
     Departments(String),
     Targets(String),
     BaseUrl(String),
     Reports(String),
+    Files(String),
+    Rules(String),
+    Sitemap(String),
+    UserAgent(String),
+    RetryAttempts(String),
+    RetryDelay(String),
+    RetryJitter(String),
+    RetryMaxDelay(String),
+    RetryStatuses(String),
+    Concurrency(String),
+    RateLimit(String),
+    CrawlDepth(String),
+    CrawlPages(String),
     Bad,
 }
 
@@ -63,6 +115,22 @@ impl Paths {
                 "Targets" => Paths::Targets(join(b, String::from(base_path))),
                 "BaseUrl" => Paths::BaseUrl(join(b, String::new())),
                 "Reports" => Paths::Reports(join(b, String::from(base_path))),
+                "Files" => Paths::Files(join(b, String::from(base_path))),
+                "Rules" => Paths::Rules(join(b, String::from(base_path))),
+                "Sitemap" => match join(b, String::new()) {
+                    s if s.starts_with("http://") || s.starts_with("https://") => Paths::Sitemap(s),
+                    s => Paths::Sitemap(String::from(base_path) + &s),
+                },
+                "UserAgent" => Paths::UserAgent(join(b, String::new())),
+                "RetryAttempts" => Paths::RetryAttempts(join(b, String::new())),
+                "RetryDelay" => Paths::RetryDelay(join(b, String::new())),
+                "RetryJitter" => Paths::RetryJitter(join(b, String::new())),
+                "RetryMaxDelay" => Paths::RetryMaxDelay(join(b, String::new())),
+                "RetryStatuses" => Paths::RetryStatuses(join_by(b, String::new(), ",")),
+                "Concurrency" => Paths::Concurrency(join(b, String::new())),
+                "RateLimit" => Paths::RateLimit(join(b, String::new())),
+                "CrawlDepth" => Paths::CrawlDepth(join(b, String::new())),
+                "CrawlPages" => Paths::CrawlPages(join(b, String::new())),
                 _ => Paths::Bad,
             }
         } else {
@@ -76,6 +144,19 @@ impl Paths {
             Paths::Departments(p) |
             Paths::Reports(p) |
             Paths::Targets(p) |
+            Paths::Files(p) |
+            Paths::Rules(p) |
+            Paths::Sitemap(p) |
+            Paths::UserAgent(p) |
+            Paths::RetryAttempts(p) |
+            Paths::RetryDelay(p) |
+            Paths::RetryJitter(p) |
+            Paths::RetryMaxDelay(p) |
+            Paths::RetryStatuses(p) |
+            Paths::Concurrency(p) |
+            Paths::RateLimit(p) |
+            Paths::CrawlDepth(p) |
+            Paths::CrawlPages(p) |
             Paths::BaseUrl(p) => String::new() + p,
             _ => String::from("bad path"),
         }
@@ -84,48 +165,442 @@ impl Paths {
     fn make_path(&self, path: String) -> String {
         self.get_path() + &path
     }
+
+    fn get_number(&self, default: usize) -> usize {
+        self.get_path().parse().unwrap_or(default)
+    }
 }
 
+#[derive(Clone)]
 struct ConfigPath {
+    // empty unless the config lists several sites
+    site: String,
     departments: Paths,
     targets: Paths,
     base_url: Paths,
     reports: Paths,
+    files: Paths,
+    rules: Paths,
+    sitemap: Paths,
+    user_agent: Paths,
+    retry_attempts: Paths,
+    retry_delay: Paths,
+    retry_jitter: Paths,
+    retry_max_delay: Paths,
+    retry_statuses: Paths,
+    concurrency: Paths,
+    rate_limit: Paths,
+    crawl_depth: Paths,
+    crawl_pages: Paths,
+    // scan rules written into config.toml rather than a rules file
+    scan: Vec<ScanRule>,
+    markdown: bool,
+    check_links: bool,
+    verbose: bool,
 }
 
 impl ConfigPath {
     fn build(mut paths: Vec<Paths>) -> Self {
-        let (mut d, mut t, mut b, mut r) = (Paths::Bad, Paths::Bad, Paths::Bad, Paths::Bad);
+        let mut config = Self {
+            site: String::new(),
+            departments: Paths::Bad,
+            targets: Paths::Bad,
+            base_url: Paths::Bad,
+            reports: Paths::Bad,
+            files: Paths::Bad,
+            rules: Paths::Bad,
+            sitemap: Paths::Bad,
+            user_agent: Paths::Bad,
+            retry_attempts: Paths::Bad,
+            retry_delay: Paths::Bad,
+            retry_jitter: Paths::Bad,
+            retry_max_delay: Paths::Bad,
+            retry_statuses: Paths::Bad,
+            concurrency: Paths::Bad,
+            rate_limit: Paths::Bad,
+            crawl_depth: Paths::Bad,
+            crawl_pages: Paths::Bad,
+            scan: Vec::new(),
+            markdown: false,
+            check_links: false,
+            verbose: false,
+        };
+
         while let Some(path) = paths.pop() {
-            (d, t, b, r) = match path {
-                Paths::Departments(_) => (path, t, b, r),
-                Paths::Targets(_) => (d, path, b, r),
-                Paths::BaseUrl(_) => (d, t, path, r),
-                Paths::Reports(_) => (d, t, b, path),
-                _ => (d, t, b, r),
+            match path {
+                Paths::Departments(_) => config.departments = path,
+                Paths::Targets(_) => config.targets = path,
+                Paths::BaseUrl(_) => config.base_url = path,
+                Paths::Reports(_) => config.reports = path,
+                Paths::Files(_) => config.files = path,
+                Paths::Rules(_) => config.rules = path,
+                Paths::Sitemap(_) => config.sitemap = path,
+                Paths::UserAgent(_) => config.user_agent = path,
+                Paths::RetryAttempts(_) => config.retry_attempts = path,
+                Paths::RetryDelay(_) => config.retry_delay = path,
+                Paths::RetryJitter(_) => config.retry_jitter = path,
+                Paths::RetryMaxDelay(_) => config.retry_max_delay = path,
+                Paths::RetryStatuses(_) => config.retry_statuses = path,
+                Paths::Concurrency(_) => config.concurrency = path,
+                Paths::RateLimit(_) => config.rate_limit = path,
+                Paths::CrawlDepth(_) => config.crawl_depth = path,
+                Paths::CrawlPages(_) => config.crawl_pages = path,
+                _ => (),
             }
         }
 
-        Self {
-            departments: d,
-            targets: t,
-            base_url: b,
-            reports: r,
+        config
+    }
+
+    fn retry_policy(&self) -> RetryPolicy {
+        RetryPolicy::build(
+            self.retry_attempts.get_number(3),
+            self.retry_delay.get_number(500) as u64,
+            self.retry_jitter.get_number(250) as u64,
+            self.retry_max_delay.get_number(60000) as u64,
+            match &self.retry_statuses {
+                Paths::RetryStatuses(s) => s.split(',').filter_map(|v| v.parse().ok()).collect(),
+                _ => Vec::new(),
+            }
+        )
+    }
+
+    fn user_agent(&self) -> String {
+        match &self.user_agent {
+            Paths::UserAgent(agent) if !agent.is_empty() => String::from(agent),
+            _ => String::from(env!("CARGO_PKG_NAME")) + "/" + env!("CARGO_PKG_VERSION"),
+        }
+    }
+
+    fn scan_rules(&self) -> ScanRules {
+        ScanRules::build(Some(self.scan_rules_prepped()))
+    }
+
+    // one config per site, or just the top level when there are none
+    fn from_settings(settings: Settings, base_path: &str) -> std::result::Result<Vec<Self>, String> {
+        let at = |p: &str| match Path::new(p).is_absolute() {
+            true => String::from(p),
+            false => String::from(base_path) + p,
+        };
+        let dir = |p: String| match p.ends_with('/') {
+            true => p,
+            false => p + "/",
+        };
+        let sitemap = |s: String| match s.starts_with("http://") || s.starts_with("https://") {
+            true => Paths::Sitemap(s),
+            false => Paths::Sitemap(at(&s)),
+        };
+
+        let mut paths = vec![
+            Paths::Departments(dir(at(&settings.output.departments))),
+            Paths::Reports(dir(at(&settings.output.reports))),
+            Paths::Files(dir(at(&settings.output.files))),
+        ];
+        if let Some(targets) = &settings.paths.targets {
+            paths.push(Paths::Targets(at(targets)));
+        }
+        if let Some(url) = settings.http.base_url {
+            paths.push(Paths::BaseUrl(dir(url)));
+        }
+        if let Some(s) = settings.paths.sitemap {
+            paths.push(sitemap(s));
+        }
+        if let Some(agent) = settings.http.user_agent {
+            paths.push(Paths::UserAgent(agent));
+        }
+        if !settings.http.retry.statuses.is_empty() {
+            let statuses: Vec<String> = settings.http.retry.statuses.iter().map(u16::to_string).collect();
+            paths.push(Paths::RetryStatuses(statuses.join(",")));
+        }
+
+        let mut number = |value: Option<usize>, path: fn(String) -> Paths| {
+            if let Some(n) = value {
+                paths.push(path(n.to_string()));
+            }
+        };
+        number(settings.http.concurrency, Paths::Concurrency);
+        number(settings.http.rate_limit, Paths::RateLimit);
+        number(settings.http.retry.attempts, Paths::RetryAttempts);
+        number(settings.http.retry.delay_ms, Paths::RetryDelay);
+        number(settings.http.retry.jitter_ms, Paths::RetryJitter);
+        number(settings.http.retry.max_delay_ms, Paths::RetryMaxDelay);
+        number(settings.crawl.depth, Paths::CrawlDepth);
+        number(settings.crawl.pages, Paths::CrawlPages);
+
+        let mut top = ConfigPath::build(paths);
+        top.with_scan(&settings.scan, &at);
+        top.markdown = settings.output.markdown;
+
+        if settings.sites.is_empty() {
+            let mut missing = Vec::new();
+            if matches!(top.targets, Paths::Bad) {
+                missing.push("missing `targets` in [paths]");
+            }
+            if matches!(top.base_url, Paths::Bad) {
+                missing.push("missing `base_url` in [http]");
+            }
+            return match missing.is_empty() {
+                true => Ok(vec![top]),
+                false => Err(missing.join("\n")),
+            };
+        }
+
+        let mut sites: Vec<Self> = Vec::new();
+        for site in settings.sites {
+            if site.name.is_empty() || site.name.contains('/') {
+                return Err(format!("site name `{}` must be a non-empty directory name", site.name));
+            }
+            if sites.iter().any(|s| s.site == site.name) {
+                return Err(format!("duplicate site `{}`", site.name));
+            }
+
+            let mut config = top.clone();
+            config.base_url = Paths::BaseUrl(dir(site.base_url));
+            config.targets = Paths::Targets(at(&site.targets));
+            config.sitemap = site.sitemap.map(sitemap).unwrap_or(Paths::Bad);
+            config.departments = Paths::Departments(match site.departments {
+                Some(d) => dir(at(&d)),
+                None => top.departments.get_path() + &site.name + "/",
+            });
+            // sites often share department names, so their files are kept apart too
+            config.files = Paths::Files(match site.files {
+                Some(f) => dir(at(&f)),
+                None => top.files.get_path() + &site.name + "/",
+            });
+            if let Some(scan) = &site.scan {
+                config.rules = Paths::Bad;
+                config.with_scan(scan, &at);
+            }
+            config.site = site.name;
+            sites.push(config);
+        }
+        Ok(sites)
+    }
+
+    fn with_scan(&mut self, scan: &config::ScanSettings, at: &dyn Fn(&str) -> String) {
+        if let Some(rules) = &scan.rules_file {
+            self.rules = Paths::Rules(at(rules));
+        }
+        self.scan = scan.content_start.clone().map(ScanRule::ContentStart)
+            .into_iter()
+            .chain(scan.content_end.clone().map(ScanRule::ContentEnd))
+            .chain(scan.rules.iter().map(|r| ScanRule::Rule(
+                Rule::build(&r.tag, &r.attribute, &r.pattern, &r.destination)
+            )))
+            .collect();
+    }
+
+    // the same settings as config.toml, with the rules file folded in
+    fn to_settings(&self, base_path: &str) -> Settings {
+        let relative = |p: &Paths| {
+            let p = p.get_path();
+            p.strip_prefix(base_path).map(String::from).unwrap_or(p)
+        };
+        let number = |p: &Paths| match p {
+            Paths::Bad => None,
+            p => p.get_path().parse().ok(),
+        };
+
+        let mut scan = config::ScanSettings::default();
+        for rule in self.scan_rules_prepped() {
+            match rule {
+                ScanRule::ContentStart(s) => scan.content_start = Some(s),
+                ScanRule::ContentEnd(e) => scan.content_end = Some(e),
+                ScanRule::Rule(r) => scan.rules.push(RuleSettings {
+                    tag: r.tag,
+                    attribute: r.attribute,
+                    pattern: r.pattern,
+                    destination: r.destination,
+                }),
+                ScanRule::Bad => (),
+            }
+        }
+
+        Settings {
+            paths: config::PathSettings {
+                targets: Some(relative(&self.targets)),
+                sitemap: match &self.sitemap {
+                    Paths::Bad => None,
+                    p => Some(relative(p)),
+                },
+            },
+            http: config::HttpSettings {
+                base_url: Some(self.base_url.get_path()),
+                user_agent: match &self.user_agent {
+                    Paths::UserAgent(a) if !a.is_empty() => Some(String::from(a)),
+                    _ => None,
+                },
+                concurrency: number(&self.concurrency),
+                rate_limit: number(&self.rate_limit),
+                retry: config::RetrySettings {
+                    attempts: number(&self.retry_attempts),
+                    delay_ms: number(&self.retry_delay),
+                    jitter_ms: number(&self.retry_jitter),
+                    max_delay_ms: number(&self.retry_max_delay),
+                    statuses: match &self.retry_statuses {
+                        Paths::RetryStatuses(s) => s.split(',').filter_map(|v| v.parse().ok()).collect(),
+                        _ => Vec::new(),
+                    },
+                },
+            },
+            crawl: config::CrawlSettings {
+                depth: number(&self.crawl_depth),
+                pages: number(&self.crawl_pages),
+            },
+            scan,
+            output: config::OutputSettings {
+                departments: relative(&self.departments),
+                reports: relative(&self.reports),
+                files: relative(&self.files),
+                markdown: self.markdown,
+            },
+            sites: Vec::new(),
         }
     }
 
-    fn prep_paths(base_path: &str) -> Option<Vec<Paths>> {
+    fn scan_rules_prepped(&self) -> Vec<ScanRule> {
+        let mut prepped = ScanRules::prep_rules(&self.rules).unwrap_or_default();
+        prepped.extend(self.scan.iter().cloned());
+        prepped
+    }
+
+    fn prep_paths(config: &str, base_path: &str) -> Option<Vec<Paths>> {
         prep_data(
-            "./config/config.txt",
+            config,
             |v: char| v == ';' || v == ',',
-            |p: &[String]| Paths::from(p, &base_path)
+            |p: &[String]| Paths::from(p, base_path)
         )
     }
 }
 
+#[derive(Clone)]
+enum ScanRule {
+    ContentStart(String),
+    ContentEnd(String),
+    Rule(Rule),
+    Bad,
+}
+
+impl ScanRule {
+    fn from(rule: &[String]) -> Self {
+        match rule {
+            [a, b @ ..] if a == "ContentStart" => ScanRule::ContentStart(join(b, String::new())),
+            [a, b @ ..] if a == "ContentEnd" => ScanRule::ContentEnd(join(b, String::new())),
+            [a, tag, attribute, pattern, destination, ..] if a == "Rule" => ScanRule::Rule(
+                Rule::build(tag, attribute, pattern, destination)
+            ),
+            [a, ..] if a.is_empty() => ScanRule::Bad,
+            _ => {
+                println!("bad scan rule: {}", join_by(rule, String::new(), " "));
+                ScanRule::Bad
+            },
+        }
+    }
+}
+
+// tags that live in the head, outside any content region
+const HEAD_TAGS: [&str; 2] = ["link", "meta"];
+
+// collect every `attribute` of `tag` whose value contains `pattern`,
+// downloading into `destination` under the Files root
+#[derive(Clone)]
+struct Rule {
+    tag: String,
+    attribute: String,
+    pattern: String,
+    destination: String,
+}
+
+impl Rule {
+    fn build(tag: &str, attribute: &str, pattern: &str, destination: &str) -> Self {
+        Self {
+            tag: String::from(tag),
+            attribute: String::from(attribute),
+            pattern: String::from(pattern),
+            destination: String::from(destination),
+        }
+    }
+
+    // head tags, like stylesheet links, are found anywhere in the page
+    fn scan(&self, data: &[u8], region: &Region) -> HashSet<String> {
+        let region = (!HEAD_TAGS.contains(&self.tag.to_ascii_lowercase().as_str())).then_some(region);
+        html::extract_attributes(data, region, &self.tag, &self.attribute, &self.pattern)
+    }
+
+    fn to_path(&self, target: &Target) -> String {
+        String::new() + &self.destination + "/" + &target.to_url()
+    }
+}
+
+struct ScanRules {
+    region: Region,
+    rules: Vec<Rule>,
+}
+
+impl ScanRules {
+    fn build(prepped: Option<Vec<ScanRule>>) -> Self {
+        let (mut start, mut end) = (String::from("id=\"content\""), String::from("class=\"layout-csun--footer\""));
+        let mut rules = Vec::new();
+        for rule in prepped.unwrap_or_default() {
+            match rule {
+                ScanRule::ContentStart(s) => start = s,
+                ScanRule::ContentEnd(e) => end = e,
+                ScanRule::Rule(r) => rules.push(r),
+                ScanRule::Bad => (),
+            }
+        }
+
+        if rules.is_empty() {
+            rules.push(Rule::build("a", "href", "/sites/default/files/", ""));
+        }
+
+        Self {
+            region: Region::new(&start, &end),
+            rules,
+        }
+    }
+
+    fn prep_rules(rules_path: &Paths) -> Option<Vec<ScanRule>> {
+        match rules_path {
+            Paths::Rules(_) => prep_data(
+                &rules_path.get_path(),
+                |v| v == ';' || v == ',',
+                ScanRule::from
+            ),
+            _ => None,
+        }
+    }
+}
+
 struct Target {
     base: String,
     extension: String,
+    depth: usize,
+    label: Label,
+}
+
+// what an extended targets file says about a target beyond where it lives
+#[derive(Default)]
+struct Label {
+    title: Option<String>,
+    owner: Option<String>,
+    priority: i64,
+    content_start: Option<String>,
+    content_end: Option<String>,
+}
+
+impl Label {
+    // the content region of this page, from its overrides or the scan rules
+    fn region(&self, rules: &Region) -> Option<Region> {
+        if self.content_start.is_none() && self.content_end.is_none() {
+            return None;
+        }
+
+        Some(Region {
+            start: self.content_start.as_deref().map(Selector::parse).unwrap_or_else(|| rules.start.clone()),
+            end: self.content_end.as_deref().map(Selector::parse).unwrap_or_else(|| rules.end.clone()),
+        })
+    }
 }
 
 impl Target {
@@ -138,22 +613,102 @@ impl Target {
 
 
         Self {
-            base: base,
+            base,
             extension: match extension {
                 Some(ext) => join_by(ext, String::new(), "/"),
                 _ => String::new()
             },
+            depth: 0,
+            label: Label::default(),
+        }
+    }
+
+    fn new(base: &str, extension: &str) -> Self {
+        Self {
+            base: String::from(base),
+            extension: String::from(extension),
+            depth: 0,
+            label: Label::default(),
+        }
+    }
+
+    // the department and extension exactly as written. unlike a line of a
+    // plain targets file, no trailing `/` is added
+    fn from_entry(entry: listing::Entry) -> Option<Self> {
+        let mut target = Target::parse(entry.path.trim())?;
+        target.label = Label {
+            title: entry.title,
+            owner: entry.owner,
+            priority: entry.priority.unwrap_or_default(),
+            content_start: entry.content_start,
+            content_end: entry.content_end,
+        };
+        Some(target)
+    }
+
+    // the inverse of from_entry
+    fn to_entry(&self) -> listing::Entry {
+        listing::Entry {
+            path: self.to_url(),
+            title: self.label.title.clone(),
+            owner: self.label.owner.clone(),
+            priority: (self.label.priority != 0).then_some(self.label.priority),
+            content_start: self.label.content_start.clone(),
+            content_end: self.label.content_end.clone(),
+        }
+    }
+
+    // the target a journaled record was made for
+    fn from_record(record: &Record) -> Option<Self> {
+        match record.entry.clone() {
+            Some(entry) => Target::from_entry(entry),
+            // journaled before entries were kept
+            None => Target::parse(&record.target),
+        }
+    }
+
+    // the target a page url maps to, if it lives under the base url
+    fn from_url(url: &Url, base_url: &str) -> Option<Self> {
+        Target::parse(url.as_str().strip_prefix(base_url)?)
+    }
+
+    // the inverse of to_url
+    fn parse(path: &str) -> Option<Self> {
+        match path.split_once('/') {
+            Some((base, extension)) if !base.is_empty() => Some(Target::new(base, extension)),
+            None if !path.is_empty() => Some(Target::new(path, "")),
+            _ => None,
         }
     }
 
+    // the line in a targets file that builds this target
+    fn to_line(&self) -> String {
+        String::from(self.to_url().trim_end_matches('/'))
+    }
+
     fn to_path(&self) -> String {
-        String::new() + &self.base + &"/"
+        String::new() + &self.base + "/"
     }
 
     fn to_url(&self) -> String {
         self.to_path() + &self.extension
     }
 
+    // where the page lives on the new site, without a file extension or a
+    // trailing index page
+    fn slug(&self) -> String {
+        let line = self.to_line();
+        let line = match line.rsplit_once('.') {
+            Some((stem, "html" | "htm" | "php" | "asp" | "aspx")) => stem,
+            _ => line.as_str(),
+        };
+        let line = match line {
+            "index" => "",
+            line => line.strip_suffix("/index").unwrap_or(line),
+        };
+        String::from("/") + line.trim_end_matches('/')
+    }
+
     fn to_store(&self) -> String {
         self.extension
             .chars()
@@ -165,7 +720,7 @@ impl Target {
                 }
                 acc
             })
-        + &".txt"
+        + ".txt"
     }
 }
 
@@ -174,26 +729,181 @@ struct Targets {
 }
 
 impl Targets {
+    // ordered so the highest priority is popped first
     fn build(prepped: Option<Vec<Target>>) -> Self {
-        Self {
-            targets: match prepped {
-                Some(p) => p,
-                _ => Vec::new(),
-            },
-        }
+        let mut targets = prepped.unwrap_or_default();
+        targets.sort_by_key(|t| t.label.priority);
+        Self { targets }
     }
 
     fn pop(&mut self) -> Option<Target> {
         self.targets.pop()
     }
 
+    fn is_empty(&self) -> bool {
+        self.targets.is_empty()
+    }
+
+    // queued behind everything already waiting to be pursued
+    fn enqueue(&mut self, target: Target) {
+        self.targets.insert(0, target)
+    }
+
+    // a plain list, or targets.csv / targets.jsonl with labels and overrides
     fn prep_targets(target_path: &Paths) -> Option<Vec<Target>> {
+        let file = target_path.get_path();
+        if file.ends_with(".csv") || file.ends_with(".jsonl") {
+            let text = read_to_string(&file).ok()?;
+            let entries = match file.ends_with(".csv") {
+                true => listing::read_csv(&text, &file),
+                false => listing::read_jsonl(&text, &file),
+            };
+            return Some(entries.into_iter().filter_map(Target::from_entry).collect());
+        }
+
         prep_data(
             &target_path.get_path(),
             |v| v == '/',
             Target::build
         )
     }
+
+    async fn prep_sitemap(paths: &ConfigPath, client: &Client) -> Option<Vec<Target>> {
+        if let Paths::Sitemap(location) = &paths.sitemap {
+            match sitemap::collect_pages(client, location).await {
+                Ok(pages) => Some(
+                    pages
+                        .iter()
+                        .filter_map(|page| Url::parse(page).ok())
+                        .filter_map(|url| Target::from_url(&url, &paths.base_url.get_path()))
+                        .collect()
+                ),
+                Err(e) => {
+                    println!("{location}: {e}");
+                    None
+                },
+            }
+        } else {
+            None
+        }
+    }
+
+    // targets listed by both sources are only pursued once
+    fn merge(prepped: Option<Vec<Target>>, other: Option<Vec<Target>>) -> Option<Vec<Target>> {
+        match (prepped, other) {
+            (Some(mut p), Some(o)) => {
+                let mut seen: HashSet<String> = p.iter().map(Target::to_line).collect();
+                p.extend(o.into_iter().filter(|t| seen.insert(t.to_line())));
+                Some(p)
+            },
+            (p, None) => p,
+            (None, o) => o,
+        }
+    }
+}
+
+// discovers internal pages from the content of pursued targets
+struct Crawl {
+    max_depth: usize,
+    max_pages: usize,
+    seen: HashSet<String>,
+    found: Vec<listing::Entry>,
+}
+
+impl Crawl {
+    fn build(paths: &ConfigPath, targets: &Targets) -> Self {
+        Self {
+            max_depth: paths.crawl_depth.get_number(3),
+            max_pages: paths.crawl_pages.get_number(500),
+            seen: targets.targets.iter().map(Target::to_line).collect(),
+            found: targets.targets.iter().rev().map(Target::to_entry).collect(),
+        }
+    }
+
+    fn discover(&mut self, content: &[u8], page: &Target, base_url: &str) -> Vec<Target> {
+        let depth = page.depth + 1;
+        if depth > self.max_depth {
+            return Vec::new();
+        }
+
+        let page_url = match Url::parse(&(String::from(base_url) + &page.to_url())) {
+            Ok(url) => url,
+            Err(e) => {
+                println!("{e}");
+                return Vec::new();
+            },
+        };
+
+        let mut links: Vec<String> = html::extract_attributes(content, None, "a", "href", "")
+            .into_iter()
+            .collect();
+        links.sort();
+
+        let mut discovered = Vec::new();
+        for link in links {
+            if self.found.len() >= self.max_pages {
+                break;
+            }
+
+            let mut url = match resolve_link(&page_url, &link) {
+                Some(url) if is_page(&url) => url,
+                _ => continue,
+            };
+            url.set_query(None);
+
+            if let Some(mut target) = Target::from_url(&url, base_url) {
+                if self.seen.insert(target.to_line()) {
+                    self.found.push(target.to_entry());
+                    target.depth = depth;
+                    discovered.push(target);
+                }
+            }
+        }
+        discovered
+    }
+
+    // pages an interrupted run already finished are not pursued again, but
+    // the links on them are, read from the copy each was stored as
+    fn resume(&mut self, done: &[Record], base_url: &str) -> Vec<Target> {
+        let pages: Vec<(&Record, Target)> = done
+            .iter()
+            .filter_map(|r| Some((r, Target::from_record(r)?)))
+            .collect();
+        for (_, page) in pages.iter() {
+            if self.seen.insert(page.to_line()) {
+                self.found.push(page.to_entry());
+            }
+        }
+
+        let mut discovered = Vec::new();
+        for (record, page) in pages.iter() {
+            if let Some(content) = record.stored.as_deref().and_then(|s| std::fs::read(s).ok()) {
+                discovered.extend(self.discover(&content, page, base_url));
+            }
+        }
+        discovered
+    }
+
+    // the discovered set as a targets.jsonl. a plain targets file would read
+    // a page like `dept/child.html` back as the directory `dept/child.html/`
+    fn to_targets_file(&self) -> Result<Bytes> {
+        let mut out = String::new();
+        for entry in self.found.iter() {
+            out = out + &serde_json::to_string(entry)? + "\n";
+        }
+        Ok(Bytes::from(out))
+    }
+}
+
+// links to documents and images are never crawled
+fn is_page(url: &Url) -> bool {
+    match url.path().rsplit('/').next().and_then(|name| name.rsplit_once('.')) {
+        Some((_, extension)) => matches!(
+            extension.to_ascii_lowercase().as_str(),
+            "html" | "htm" | "php" | "asp" | "aspx"
+        ),
+        None => true,
+    }
 }
 
 struct Department {
@@ -205,9 +915,9 @@ struct Department {
 impl Department {
     fn build(path: Target, today: Today, base: Paths) -> Self {
         Self {
-            base: base,
-            path: path,
-            today: today,
+            base,
+            path,
+            today,
         }
     }
 
@@ -216,11 +926,11 @@ impl Department {
     }
 
     fn storage_location_today(&self) -> String {
-        self.location() + &self.today.date.get() + &"/"
+        self.location() + &self.today.date.get() + "/"
     }
     
     fn storage_location_now(&self) -> String {
-        self.storage_location_today() + &self.today.time.get() + &"/"
+        self.storage_location_today() + &self.today.time.get() + "/"
     }
 
     fn file_location(&self) -> String {
@@ -228,9 +938,10 @@ impl Department {
     }
 
     fn create_path(&self) -> Result<()> {
+        // a site's department root may not exist yet
         let loc = self.location();
         if !Path::new(&loc).is_dir() {
-            create_dir(loc)?;
+            create_dir_all(loc)?;
         }
 
         let date = self.storage_location_today();
@@ -246,240 +957,920 @@ impl Department {
         Ok(())
     }
 
-    fn store(&self, data: Bytes) -> String {
-        if let Err(e) = write_file(data, self.file_location()) {
-            println!("{e}");
-            String::from("No data for ") + &self.path.to_url()   
-        } else {
-            self.file_location()
-        }
+    fn store(&self, data: Bytes) -> Result<String> {
+        write_file(data, self.file_location())?;
+        Ok(self.file_location())
     }
 
-    fn destroy(self) -> (Paths, Target, Today) {
-        (self.base, self.path, self.today)
+    // the newest copy of this target stored by an earlier run
+    fn previous_copy(&self) -> Option<String> {
+        let now = self.storage_location_now();
+        snapshots(&self.location())
+            .into_iter()
+            .rev()
+            .filter(|(_, dir)| dir != &now)
+            .find_map(|(_, dir)| stored_copy(&(dir + &self.path.to_store())))
     }
-}
 
-struct Report {
-    info: Department,
-    data: Vec<String>,
-}
-
-impl Report {
-    fn new(info: Department) -> Self {
-        Self {
-            info: info,
-            data: Vec::new(),
-        }
+    // the cleaned content region, next to the raw copy
+    fn content_location(&self) -> String {
+        let raw = self.file_location();
+        String::from(raw.strip_suffix(".txt").unwrap_or(&raw)) + ".content.html"
     }
 
-    fn add(&mut self, report: String) -> () {
-        self.data.push(report)
+    fn store_content(&self, fragment: &str) -> Result<String> {
+        write_file(Bytes::from(String::from(fragment)), self.content_location())?;
+        Ok(self.content_location())
     }
 
-    fn build(self) -> Result<String> {
-        match self.info.create_path() {
-            Ok(_) => Ok(
-                self.info.store(
-                    Bytes::from(
-                        self.data
-                            .iter()
-                            .fold(String::new(), |acc, item| {
-                                acc + item + ",\n"
-                            })
-                            .as_bytes()
-                            .to_owned()
-                    )
-                )
-            ),
-            Err(e) => Err(e),
-        }
+    fn markdown_location(&self) -> String {
+        let raw = self.file_location();
+        String::from(raw.strip_suffix(".txt").unwrap_or(&raw)) + ".md"
     }
-}
-
-pub struct Manager;
-
-impl Manager {
-    pub fn run(base_path: &str) -> Result<String> {
-        if !Path::new(&base_path).is_dir() {
-            return Err(Error::from("Invalid base path"))
-        }
 
-        if let Some(c) = ConfigPath::prep_paths(base_path) {
-            let paths = ConfigPath::build(c);
+    fn store_markdown(&self, document: &str) -> Result<String> {
+        write_file(Bytes::from(String::from(document)), self.markdown_location())?;
+        Ok(self.markdown_location())
+    }
 
-            let isolated_targets = Targets::prep_targets(&paths.targets);
+    // front matter for the markdown copy of a page
+    fn front_matter(&self, title: &str, url: &str) -> String {
+        let captured = match &self.today.time {
+            Daily::Time(t) => Utc.timestamp_opt(*t, 0).single().map(|d| d.to_rfc3339()).unwrap_or_default(),
+            other => other.get(),
+        };
+        markdown::front_matter(&[
+            ("title", title),
+            ("source_url", url),
+            ("captured", &captured),
+            ("department", &self.path.base),
+        ])
+    }
 
-            let targets = Targets::build(isolated_targets);
-        
-            let report = pursue_targets(targets, paths)?;
+    // an unchanged target points at its previous copy rather than duplicating it
+    fn store_reference(&self, previous: &str) -> Result<()> {
+        write_file(Bytes::from(String::from(previous)), self.file_location() + ".ref")
+    }
+}
 
-            report.build()
-        } else {
-            Err(Error::from("Missing config file. Make sure config.txt exists in config/. Make sure it has appropriate content."))
+// every `<date>/<timestamp>/` folder under a department, oldest first
+fn snapshots(location: &str) -> Vec<(i64, String)> {
+    let mut found = Vec::new();
+    for date in read_dir(location).into_iter().flatten().flatten() {
+        for time in read_dir(date.path()).into_iter().flatten().flatten() {
+            let stamp = time.file_name().to_string_lossy().parse::<i64>();
+            if let (Ok(stamp), true) = (stamp, time.path().is_dir()) {
+                found.push((stamp, time.path().to_string_lossy().into_owned() + "/"));
+            }
         }
     }
+    found.sort();
+    found
 }
 
-fn read_file(path: String) -> Result<BufReader<File>>{
-    let file = File::open(path)?;
-    Ok(BufReader::new(file))
+// every capture timestamp found under the departments root, oldest first
+fn snapshot_stamps(departments: &Paths) -> Vec<i64> {
+    let mut stamps: Vec<i64> = read_dir(departments.get_path())
+        .into_iter()
+        .flatten()
+        .flatten()
+        .flat_map(|dept| snapshots(&dept.path().to_string_lossy()))
+        .map(|(stamp, _)| stamp)
+        .collect();
+    stamps.sort();
+    stamps.dedup();
+    stamps
 }
 
-fn rest<T>(data: &[T]) -> &[T] {
-    match data {
-        [_, b @ ..] => b,
-        _ => &[]
-    }
-}
-
-fn bytes_match(a: &[u8], b: &[u8], count: usize) -> usize {
-    match a {
-        [c, d @ ..] if !b.is_empty() && b.first().unwrap() == c => bytes_match(d, rest(b), count + 1),
-        _ => count
-    }
-}
-
-fn proper_scan_bytes(data: Bytes, content_marker: &str, end_content_marker: &str, tag_marker: &str, attribute_marker: &str, pattern_marker: &str, deliminator_marker: &str) -> HashSet<Vec<u8>> {
-    let l = data.len();
-    let mut i = 0;
-    let mut j = 0;
-    let mut k;
-    let mut scan = HashSet::new();
-    // where to start scanning
-    let content_marker = content_marker.as_bytes();
-    let cl = content_marker.len();
-    // where to stop scanning
-    let end_content_marker = end_content_marker.as_bytes();
-    let el = end_content_marker.len();
-    // tag to find. syntax: <tag
-    let tag_marker = tag_marker.as_bytes();
-    let tl = tag_marker.len();
-    // attribute in tag
-    let attribute_marker = attribute_marker.as_bytes();
-    let al = attribute_marker.len();
-    // pattern to search for within value of attribute
-    let pattern_marker = pattern_marker.as_bytes();
-    let pl = pattern_marker.len();
-    // deliminates the end of an attribute e.g. " or the space character
-    let deliminator_marker = deliminator_marker.as_bytes();
-    let dl = deliminator_marker.len();
-
-    // look for where to start scanning
-    while i + cl < l {
-        j = bytes_match(&data[i..i+cl], &content_marker, j);
-        i += j;
-        if j == cl {
-            break;
-        }
-        j = 0;
-        i += 1;
-    }
-    // start scanning for tag marker. Also check for end content marker
-    while i + tl < l {
-        j = bytes_match(&data[i..i+tl], &tag_marker, 0);
-        if j == tl && i + j + al < l {
-            i += j;
-            // searching for attribute
-            loop {
-                j = bytes_match(&data[i..i+al], &attribute_marker, 0);
-                i += j + 1;
-                if i + al >= l || j == al {
-                    break;
-                }
-            }
-            if j == al && i + j + 1 + pl < l {
-                // {attribute}=" therefore i += j + 1
-                // doesn't work for {attribute}= e.g. width=300
-                i += 1;
-                j = i;
-
-                // searching for end of attribute
-                loop {
-                    k = bytes_match(&data[j..j+dl], &deliminator_marker, 0);
-                    j += k + 1;
-                    if j + dl > l || k == dl {
-                        break;
-                    }
-                }
-
-                // this was easier than finding pattern before deliminator
-                // but this forces us to rescan :(
+fn resolve_stamp(stamp: &str, stamps: &[i64]) -> Result<i64> {
+    let found = match stamp {
+        "latest" => stamps.last().copied(),
+        "previous" => stamps.iter().rev().nth(1).copied(),
+        s => s.parse().ok().filter(|s| stamps.contains(s)),
+    };
+    found.ok_or_else(|| Error::from(format!("No snapshot found for {stamp}")))
+}
 
-                // check the pattern we're looking for is in attribute value
-                let mut temp = 0;
-                while temp < j - 2 - i && j - 2 - (i + temp) > pl && bytes_match(&data[i+temp..j-2], &pattern_marker, 0) != pl {
-                    temp += 1;
+// the pages captured at `stamp`, keyed by department and stored name
+fn snapshot_pages(departments: &Paths, stamp: i64) -> BTreeMap<String, String> {
+    let mut pages = BTreeMap::new();
+    for dept in read_dir(departments.get_path()).into_iter().flatten().flatten() {
+        let name = dept.file_name().to_string_lossy().into_owned();
+        let dirs = snapshots(&dept.path().to_string_lossy())
+            .into_iter()
+            .filter(|(s, _)| *s == stamp);
+        for (_, dir) in dirs {
+            for file in read_dir(&dir).into_iter().flatten().flatten() {
+                let file = file.file_name().to_string_lossy().into_owned();
+                let store = file.strip_suffix(".ref").unwrap_or(&file);
+                if !store.ends_with(".txt") {
+                    continue;
                 }
-
-                // store value of atrribute into scan
-                if j <= l && j - 2 - (i + temp) > pl {
-                    scan.insert(Vec::from(&data[i..j-2]));
+                if let Some(path) = stored_copy(&(dir.clone() + store)) {
+                    pages.insert(name.clone() + "/" + store, path);
                 }
-            } else {
-                j = i;
-            }
-            i = j;
-        } else if i + el < l {
-            // checking if we can stop scanning
-            k = bytes_match(&data[i..i+el], &end_content_marker, 0);
-            if k == el {
-                break;
             }
-            // trying to save us from rechecking same bytes
-            i += j.max(k) + 1;
-        } else {
-            break;
         }
     }
-    scan
+    pages
 }
 
-// temporary solution
-async fn download_files(scan: HashSet<Vec<u8>>, path: String) -> Result<()> {
-    let mut base_path = String::from("T:/Web_Migration/files/");
-    for part in path.split("/") {
-        base_path += part;
-        if !Path::new(&base_path).is_dir() {
-            create_dir(&base_path)?;
+// the content region of a stored page, if it is found there
+fn content_region(path: &str, rules: &ScanRules) -> Result<Option<String>> {
+    let region = html::region_html(&std::fs::read(path)?, Some(&rules.region));
+    Ok(Some(region).filter(|r| !r.trim().is_empty()))
+}
+
+fn content_lines(region: &str) -> Vec<&str> {
+    region.lines().map(str::trim).filter(|l| !l.is_empty()).collect()
+}
+
+// the diff of one site's department store, and the stamps it compared
+fn diff_site(paths: &ConfigPath, from: &str, to: &str) -> Result<(String, i64, i64)> {
+    let rules = paths.scan_rules();
+
+    let stamps = snapshot_stamps(&paths.departments);
+    let (from, to) = (resolve_stamp(from, &stamps)?, resolve_stamp(to, &stamps)?);
+    let (old, new) = (snapshot_pages(&paths.departments, from), snapshot_pages(&paths.departments, to));
+
+    let added: Vec<&String> = new.keys().filter(|k| !old.contains_key(*k)).collect();
+    let removed: Vec<&String> = old.keys().filter(|k| !new.contains_key(*k)).collect();
+    let mut changed = Vec::new();
+    for (page, old_path) in old.iter() {
+        if let Some(new_path) = new.get(page) {
+            // a page missing the region would look unchanged, so the whole
+            // of both captures is compared instead
+            let (a, b, note) = match (content_region(old_path, &rules)?, content_region(new_path, &rules)?) {
+                (Some(a), Some(b)) => (a, b, ""),
+                _ => (read_to_string(old_path)?, read_to_string(new_path)?, " (content region not found, whole page compared)"),
+            };
+            if a != b {
+                let (a, b): (Vec<&str>, Vec<&str>) = (content_lines(&a), content_lines(&b));
+                changed.push((page, note, diff::render(&diff::diff_lines(&a, &b), 2)));
+            }
         }
-        base_path += "/";    
     }
-    let path = base_path;
 
-    for target in scan.iter() {
-        let url = match target.first() {
-            Some(b) if b == &b'/' => String::from("https://www.csun.edu") + String::from_utf8_lossy(target).as_ref(),
-            Some(_) => String::from_utf8_lossy(target).as_ref().to_string(),
-            _ => continue
-        };
-        let response = reqwest::get(url).await?;
-        let fname = response
-            .url()
-            .path_segments()
-            .and_then(|segments| segments.last())
-            .and_then(|name| if name.is_empty() { None } else { name.split("?").next() })
-            .and_then(|name| Some(name.replace("%20", " ")))
-            .unwrap_or(String::from("tmp.bin"));
-
-        println!("file to download: '{}'", fname);
-        let fname = path.clone() + &fname;
-        let content = response.bytes().await?;
-        write_file(content, fname)?;
+    let list = |title: &str, pages: &[&String]| pages
+        .iter()
+        .fold(format!("{title} ({})\n", pages.len()), |acc, p| acc + "  " + p + "\n");
+    let mut out = format!("Snapshot diff {from} -> {to}\n\n");
+    out += &list("Added", &added);
+    out += &list("Removed", &removed);
+    out += &list("Changed", &changed.iter().map(|(p, _, _)| *p).collect::<Vec<&String>>());
+    for (page, note, text) in changed.iter() {
+        out = out + "\n== " + page + note + " ==\n" + text;
     }
-    Ok(())
+    Ok((out, from, to))
 }
 
-// async fn download_images(scan: HashSet<Vec<u8>>, path: String) -> Result<()> {
-//     let base_path = "C:/Users/Vel4ta/Desktop/mtc/";
-//     let new_path = &(String::from(base_path) + &path);
-//     if !Path::new(new_path).is_dir() {
-//         create_dir(new_path)?;
-//     }
-//     for item in scan {
-//         let mut url = item.iter().fold(&mut String::new(),|acc, x| {acc.push(*x as char); acc}).to_owned();
-//         if item.iter().next().unwrap() == &b'/' {
-//             url = String::from("https://www.csun.edu") + &url;
+// the stored file at `path`, following the reference left by an unchanged fetch
+fn stored_copy(path: &str) -> Option<String> {
+    if Path::new(path).is_file() {
+        return Some(String::from(path));
+    }
+
+    read_to_string(String::from(path) + ".ref")
+        .ok()
+        .map(|r| String::from(r.trim()))
+        .filter(|r| Path::new(r).is_file())
+}
+
+// the cache validators saved next to a stored page
+#[derive(Default)]
+struct Validators {
+    etag: Option<String>,
+    last_modified: Option<String>,
+}
+
+impl Validators {
+    fn from_headers(headers: &HeaderMap) -> Self {
+        let get = |name| headers.get(name).and_then(|v: &HeaderValue| v.to_str().ok()).map(String::from);
+        Self {
+            etag: get(ETAG),
+            last_modified: get(LAST_MODIFIED),
+        }
+    }
+
+    fn read(stored: &str) -> Self {
+        let mut validators = Validators::default();
+        for line in read_to_string(String::from(stored) + ".meta").unwrap_or_default().lines() {
+            match line.split_once(':') {
+                Some(("ETag", v)) => validators.etag = Some(String::from(v.trim())),
+                Some(("Last-Modified", v)) => validators.last_modified = Some(String::from(v.trim())),
+                _ => (),
+            }
+        }
+        validators
+    }
+
+    fn write(&self, stored: &str) -> Result<()> {
+        let mut meta = String::new();
+        if let Some(etag) = &self.etag {
+            meta = meta + "ETag: " + etag + "\n";
+        }
+        if let Some(modified) = &self.last_modified {
+            meta = meta + "Last-Modified: " + modified + "\n";
+        }
+
+        if meta.is_empty() {
+            Ok(())
+        } else {
+            write_file(Bytes::from(meta), String::from(stored) + ".meta")
+        }
+    }
+
+    fn apply(&self, mut request: RequestBuilder) -> RequestBuilder {
+        if let Some(etag) = &self.etag {
+            request = request.header(IF_NONE_MATCH, etag);
+        }
+        if let Some(modified) = &self.last_modified {
+            request = request.header(IF_MODIFIED_SINCE, modified);
+        }
+        request
+    }
+}
+
+// the files downloaded for a stored copy, a tab separated url and file per
+// line, so a later run that finds the page unchanged can point at them again
+fn write_saved_files(stored: &str, assets: &[(Url, String)]) -> Result<()> {
+    if assets.is_empty() {
+        return Ok(());
+    }
+    let lines = assets.iter().fold(String::new(), |acc, (url, file)| acc + url.as_str() + "\t" + file + "\n");
+    write_file(Bytes::from(lines), String::from(stored) + ".files")
+}
+
+fn read_saved_files(stored: &str) -> Vec<(Url, String)> {
+    read_to_string(String::from(stored) + ".files")
+        .unwrap_or_default()
+        .lines()
+        .filter_map(|line| line.split_once('\t'))
+        .filter(|(_, file)| Path::new(file).is_file())
+        .filter_map(|(url, file)| Some((Url::parse(url).ok()?, String::from(file))))
+        .collect()
+}
+
+struct Report {
+    info: Department,
+    site: String,
+    base_url: String,
+    data: Vec<Record>,
+    attachments: Vec<(String, Bytes)>,
+}
+
+impl Report {
+    fn new(paths: &ConfigPath, today: Today) -> Self {
+        Report::named(paths, today, "reports")
+    }
+
+    // a report written under its own directory of the reports root
+    fn named(paths: &ConfigPath, today: Today, name: &str) -> Self {
+        Self {
+            info: Department::build(Target::new(name, ""), today, paths.reports.clone()),
+            site: paths.site.clone(),
+            base_url: paths.base_url.get_path(),
+            data: Vec::new(),
+            attachments: Vec::new(),
+        }
+    }
+
+    fn add(&mut self, record: Record) {
+        self.data.push(record)
+    }
+
+    // extra files written next to the report
+    fn attach(&mut self, name: &str, data: Bytes) {
+        self.attachments.push((String::from(name), data))
+    }
+
+    // each site of a run gets its own directory
+    fn location(&self) -> String {
+        match self.site.is_empty() {
+            true => self.info.storage_location_now(),
+            false => self.info.storage_location_now() + &self.site + "/",
+        }
+    }
+
+    fn build(self) -> Result<String> {
+        self.info.create_path()?;
+        let location = self.location();
+        if !Path::new(&location).is_dir() {
+            create_dir(&location)?;
+        }
+
+        write_file(
+            Bytes::from(
+                self.data
+                    .iter()
+                    .fold(String::from(Record::CSV_HEADER) + "\n", |acc, item| {
+                        acc + &item.to_csv() + "\n"
+                    })
+            ),
+            location.clone() + "report.csv"
+        )?;
+        write_file(Bytes::from(serde_json::to_vec_pretty(&self.data)?), location.clone() + "report.json")?;
+
+        let rewrites: Vec<String> = self.data
+            .iter()
+            .flat_map(|record| record.rewrites.iter().map(move |r| [
+                record::csv_field(&record.url),
+                String::from(r.kind.name()),
+                record::csv_field(&r.from),
+                record::csv_field(&r.to),
+            ].join(",")))
+            .collect();
+        if !rewrites.is_empty() {
+            write_file(Bytes::from(String::from("page,kind,from,to\n") + &rewrites.join("\n") + "\n"), location.clone() + "rewrites.csv")?;
+        }
+
+        for (name, data) in self.attachments {
+            write_file(data, location.clone() + &name)?;
+        }
+
+        Ok(location)
+    }
+
+    // the reports of every site in a run, with sites.csv summing them up
+    // when there is more than one
+    fn build_all(reports: Vec<Report>) -> Result<Outcome> {
+        let mut outcome = Outcome { report: String::new(), pursued: 0, failed: 0 };
+        let mut summary = String::from("site,base_url,pursued,failed,report\n");
+        let mut grouped = None;
+
+        for report in reports {
+            let site = Outcome::from(&report, report.location());
+            summary += &[
+                record::csv_field(&report.site),
+                record::csv_field(&report.base_url),
+                site.pursued.to_string(),
+                site.failed.to_string(),
+                record::csv_field(&site.report),
+            ].join(",");
+            summary += "\n";
+
+            outcome.pursued += site.pursued;
+            outcome.failed += site.failed;
+            outcome.report = site.report;
+            if !report.site.is_empty() {
+                grouped = Some(report.info.storage_location_now());
+            }
+            report.build()?;
+        }
+
+        if let Some(location) = grouped {
+            write_file(Bytes::from(summary), location.clone() + "sites.csv")?;
+            outcome.report = location;
+        }
+        Ok(outcome)
+    }
+}
+
+// overrides from the command line, applied on top of the config file
+#[derive(Default, Clone)]
+pub struct Options {
+    pub base_url: Option<String>,
+    pub targets: Option<String>,
+    pub concurrency: Option<usize>,
+    pub site: Option<String>,
+    pub resume: Option<String>,
+    pub markdown: bool,
+    pub check_links: bool,
+    pub verbose: bool,
+}
+
+impl Options {
+    fn apply(&self, paths: &mut ConfigPath) {
+        if let Some(url) = &self.base_url {
+            paths.base_url = Paths::BaseUrl(String::from(url.trim_end_matches('/')) + "/");
+        }
+        if let Some(targets) = &self.targets {
+            paths.targets = Paths::Targets(String::from(targets));
+        }
+        if let Some(c) = self.concurrency {
+            paths.concurrency = Paths::Concurrency(c.to_string());
+        }
+        paths.markdown |= self.markdown;
+        paths.check_links = self.check_links;
+        paths.verbose = self.verbose;
+    }
+
+    // config keys these overrides stand in for
+    fn provides(&self) -> Vec<&'static str> {
+        let mut keys = Vec::new();
+        if self.base_url.is_some() {
+            keys.push("BaseUrl");
+        }
+        if self.targets.is_some() {
+            keys.push("Targets");
+        }
+        if self.concurrency.is_some() {
+            keys.push("Concurrency");
+        }
+        keys
+    }
+}
+
+// what a run did. disallowed targets were skipped on purpose, so they are
+// not counted as failures
+pub struct Outcome {
+    pub report: String,
+    pub pursued: usize,
+    pub failed: usize,
+}
+
+impl Outcome {
+    fn from(report: &Report, location: String) -> Self {
+        Self {
+            report: location,
+            pursued: report.data.len(),
+            failed: report.data
+                .iter()
+                .filter(|r| r.error_kind.is_some_and(|k| k != Failure::Disallowed))
+                .count(),
+        }
+    }
+}
+
+pub struct Manager;
+
+impl Manager {
+    // `config` defaults to <base_path>/config/config.toml or config.txt
+    pub fn run(base_path: &str, config: Option<&str>) -> Result<String> {
+        Ok(Manager::run_with(base_path, config, &Options::default())?.report)
+    }
+
+    // like run, but also pursues internal pages linked from each target
+    pub fn crawl(base_path: &str, config: Option<&str>) -> Result<String> {
+        Ok(Manager::crawl_with(base_path, config, &Options::default())?.report)
+    }
+
+    // runs on the caller's runtime with the caller's client. robots.txt is
+    // matched against the configured UserAgent, so the client should send it too
+    pub async fn run_async(base_path: &str, config: Option<&str>, client: Client) -> Result<String> {
+        Ok(Manager::run_async_with(base_path, config, &Options::default(), client).await?.report)
+    }
+
+    pub async fn crawl_async(base_path: &str, config: Option<&str>, client: Client) -> Result<String> {
+        Ok(Manager::crawl_async_with(base_path, config, &Options::default(), client).await?.report)
+    }
+
+    // like run, with command line overrides, telling how many targets failed
+    pub fn run_with(base_path: &str, config: Option<&str>, options: &Options) -> Result<Outcome> {
+        Manager::block_on(base_path, config, options, false)
+    }
+
+    pub fn crawl_with(base_path: &str, config: Option<&str>, options: &Options) -> Result<Outcome> {
+        Manager::block_on(base_path, config, options, true)
+    }
+
+    pub async fn run_async_with(base_path: &str, config: Option<&str>, options: &Options, client: Client) -> Result<Outcome> {
+        let sites = Manager::configure(base_path, config, options)?;
+        Manager::start(sites, client, false, options.resume.as_deref()).await
+    }
+
+    pub async fn crawl_async_with(base_path: &str, config: Option<&str>, options: &Options, client: Client) -> Result<Outcome> {
+        let sites = Manager::configure(base_path, config, options)?;
+        Manager::start(sites, client, true, options.resume.as_deref()).await
+    }
+
+    fn block_on(base_path: &str, config: Option<&str>, options: &Options, crawl: bool) -> Result<Outcome> {
+        let sites = Manager::configure(base_path, config, options)?;
+        let (client, rt) = a_client_and_runtime(&sites[0].user_agent())?;
+        rt.block_on(Manager::start(sites, client, crawl, options.resume.as_deref()))
+    }
+
+    // writes the report of an earlier run again from its journal
+    pub fn report(base_path: &str, run_id: &str, config: Option<&str>, options: &Options) -> Result<Outcome> {
+        let sites = Manager::configure(base_path, config, options)?;
+        Report::build_all(journaled_reports(&sites, run_id, "reports")?)
+    }
+
+    // the results of an earlier run in one directory per site: its report
+    // and the redirect maps to where its pages and files now live. refused,
+    // like redirects, when two old urls would land on one new path
+    pub fn export(base_path: &str, run_id: &str, config: Option<&str>, options: &Options) -> Result<Outcome> {
+        let sites = Manager::configure(base_path, config, options)?;
+        let mut reports = journaled_reports(&sites, run_id, "export")?;
+
+        let mut collisions = Vec::new();
+        for (report, paths) in reports.iter_mut().zip(sites.iter()) {
+            let redirects = redirects_of(&report.data, paths);
+            collisions.extend(site_collisions(&redirects, paths));
+            report.attach("redirects.nginx.conf", Bytes::from(redirects::nginx(&redirects)));
+            report.attach("redirects.apache.conf", Bytes::from(redirects::apache(&redirects)));
+            report.attach("redirects.csv", Bytes::from(redirects::csv(&redirects)));
+        }
+        if !collisions.is_empty() {
+            return Err(format!("Redirects collide:\n{}", collisions.join("\n")).into());
+        }
+
+        Report::build_all(reports)
+    }
+
+    // the settings a run would use, after overrides
+    pub fn validate(base_path: &str, config: Option<&str>, options: &Options) -> Result<Vec<String>> {
+        let sites = Manager::configure(base_path, config, options)?;
+        let mut problems = Vec::new();
+        let mut lines = Vec::new();
+        for paths in sites.iter() {
+            for (key, path) in [("Targets", &paths.targets), ("Rules", &paths.rules)] {
+                if !matches!(path, Paths::Bad) && !Path::new(&path.get_path()).is_file() {
+                    problems.push(format!("{key} file not found: {}", path.get_path()));
+                }
+            }
+
+            if !paths.site.is_empty() {
+                lines.push(format!("[{}]", paths.site));
+            }
+            lines.extend([
+                format!("Departments: {}", paths.departments.get_path()),
+                format!("Targets: {}", paths.targets.get_path()),
+                format!("BaseUrl: {}", paths.base_url.get_path()),
+                format!("Reports: {}", paths.reports.get_path()),
+                format!("Files: {}", paths.files.get_path()),
+                format!("UserAgent: {}", paths.user_agent()),
+                format!("Concurrency: {}", paths.concurrency.get_number(3)),
+                format!("RateLimit: {}", paths.rate_limit.get_number(3)),
+            ]);
+        }
+        if !problems.is_empty() {
+            return Err(ErrorKind::Config(problems.join("\n")).into());
+        }
+
+        Ok(lines)
+    }
+
+    // the urls a run would pursue, from the targets file and sitemap
+    pub fn targets(base_path: &str, config: Option<&str>, options: &Options) -> Result<Vec<String>> {
+        let sites = Manager::configure(base_path, config, options)?;
+        let (client, rt) = a_client_and_runtime(&sites[0].user_agent())?;
+
+        let mut urls = Vec::new();
+        for paths in sites.iter() {
+            let targets = rt.block_on(async {
+                Targets::merge(
+                    Targets::prep_targets(&paths.targets),
+                    Targets::prep_sitemap(paths, &client).await
+                )
+            });
+            urls.extend(targets
+                .unwrap_or_default()
+                .iter()
+                .map(|t| paths.base_url.make_path(t.to_url())));
+        }
+        Ok(urls)
+    }
+
+    // writes config.toml next to a config.txt, with its rules file folded in
+    pub fn convert(base_path: &str, config: Option<&str>) -> Result<String> {
+        let legacy = match config {
+            Some(c) => PathBuf::from(c),
+            None => Path::new(base_path).join("config").join("config.txt"),
+        };
+        if legacy.extension().is_some_and(|e| e == "toml") {
+            return Err(ErrorKind::Config(format!("{} is already a toml config", legacy.display())).into())
+        }
+
+        let paths = Manager::configure(base_path, Some(&legacy.to_string_lossy()), &Options::default())?.remove(0);
+        let location = legacy.with_file_name("config.toml");
+        if location.exists() {
+            return Err(Error::from(format!("{} already exists", location.display())))
+        }
+
+        let text = paths.to_settings(base_path).to_toml()?;
+        write_file(Bytes::from(text), location.to_string_lossy().into_owned())?;
+        Ok(location.to_string_lossy().into_owned())
+    }
+
+    // redirect maps from the urls of a run to where their pages and files
+    // now live, for nginx, apache and as csv. refused when two old urls
+    // would land on one new path
+    pub fn redirects(base_path: &str, run_id: &str, config: Option<&str>, options: &Options) -> Result<String> {
+        let sites = Manager::configure(base_path, config, options)?;
+        let records = Journal::latest(&sites[0].reports.get_path(), run_id)?;
+
+        let mut maps = Vec::new();
+        let mut collisions = Vec::new();
+        for paths in sites.iter() {
+            let site: Vec<Record> = records.iter().filter(|r| r.site == paths.site).cloned().collect();
+            let redirects = redirects_of(&site, paths);
+            collisions.extend(site_collisions(&redirects, paths));
+            maps.push((paths.site.clone(), redirects));
+        }
+        if !collisions.is_empty() {
+            return Err(format!("Redirects collide:\n{}", collisions.join("\n")).into());
+        }
+
+        let d = Department::build(Target::new("redirects", ""), Today::build(), sites[0].reports.clone());
+        d.create_path()?;
+        let location = d.storage_location_now();
+        for (site, redirects) in maps {
+            let at = match site.is_empty() {
+                true => location.clone(),
+                false => location.clone() + &site + "/",
+            };
+            create_dir_all(&at)?;
+            write_file(Bytes::from(redirects::nginx(&redirects)), at.clone() + "redirects.nginx.conf")?;
+            write_file(Bytes::from(redirects::apache(&redirects)), at.clone() + "redirects.apache.conf")?;
+            write_file(Bytes::from(redirects::csv(&redirects)), at.clone() + "redirects.csv")?;
+        }
+        Ok(location)
+    }
+
+    // compares two captures, each a timestamp or `latest`/`previous`, site by site
+    pub fn diff(base_path: &str, from: &str, to: &str, config: Option<&str>, options: &Options) -> Result<String> {
+        let sites = Manager::configure(base_path, config, options)?;
+
+        let mut out = String::new();
+        let mut stamps = None;
+        for paths in sites.iter() {
+            if !paths.site.is_empty() {
+                out = out + "# " + &paths.site + "\n";
+            }
+            // a site with nothing to compare should not hide the others
+            match diff_site(paths, from, to) {
+                Ok((text, from, to)) => {
+                    out += &text;
+                    stamps.get_or_insert((from, to));
+                },
+                Err(e) if sites.len() > 1 => out += &e.to_string(),
+                Err(e) => return Err(e),
+            }
+            out += "\n";
+        }
+
+        let Some((from, to)) = stamps else {
+            return Err(format!("No site has captures to compare\n{}", out.trim_end()).into());
+        };
+        let d = Department::build(Target::new("diff", ""), Today::build(), sites[0].reports.clone());
+        d.create_path()?;
+        let location = d.storage_location_now() + &format!("{from}-{to}.txt");
+        write_file(Bytes::from(out), location.clone())?;
+        Ok(location)
+    }
+
+    // one config per site, narrowed to `--site` when given
+    fn configure(base_path: &str, config: Option<&str>, options: &Options) -> Result<Vec<ConfigPath>> {
+        if !Path::new(&base_path).is_dir() {
+            return Err(ErrorKind::Config(String::from("Invalid base path")).into())
+        }
+
+        // relative to the base path like every path inside it, unless given.
+        // config.toml is preferred when both exist
+        let config = match config {
+            Some(c) => PathBuf::from(c),
+            None => {
+                let dir = Path::new(base_path).join("config");
+                match dir.join("config.toml").is_file() {
+                    true => dir.join("config.toml"),
+                    false => dir.join("config.txt"),
+                }
+            },
+        };
+        let text = match read_to_string(&config) {
+            Ok(text) => text,
+            Err(e) => return Err(ErrorKind::Config(format!("Cannot read config file {}: {e}", config.display())).into()),
+        };
+        let file = config.display().to_string();
+
+        if let Some(problem) = options.base_url.as_deref().and_then(config::invalid_url) {
+            return Err(ErrorKind::Config(format!("--base-url: {problem}")).into())
+        }
+
+        let mut sites = if config.extension().is_some_and(|e| e == "toml") {
+            match Settings::parse(&text).and_then(|settings| ConfigPath::from_settings(settings, base_path)) {
+                Ok(sites) => sites,
+                Err(e) => return Err(ErrorKind::Config(format!("{file}: {e}")).into()),
+            }
+        } else {
+            // every problem is reported at once, before anything is fetched
+            let (warnings, errors): (Vec<_>, Vec<_>) = config::check(&text, &options.provides())
+                .into_iter()
+                .partition(|p| p.warning);
+            for warning in warnings {
+                println!("{}", warning.describe(&file));
+            }
+            if !errors.is_empty() {
+                let errors: Vec<String> = errors.iter().map(|e| e.describe(&file)).collect();
+                return Err(ErrorKind::Config(errors.join("\n")).into())
+            }
+
+            match ConfigPath::prep_paths(&file, base_path) {
+                Some(c) => vec![ConfigPath::build(c)],
+                None => return Err(ErrorKind::Config(format!("Cannot read config file {file}")).into()),
+            }
+        };
+
+        if let Some(site) = &options.site {
+            sites.retain(|paths| &paths.site == site);
+            if sites.is_empty() {
+                return Err(ErrorKind::Config(format!("{file}: no site named `{site}`")).into())
+            }
+        }
+        if sites.len() > 1 && (options.base_url.is_some() || options.targets.is_some()) {
+            return Err(ErrorKind::Config(String::from("--base-url and --targets need --site when the config lists several sites")).into())
+        }
+
+        for paths in sites.iter_mut() {
+            options.apply(paths);
+        }
+        // without it every rule would quietly fall back to the default one
+        for paths in sites.iter() {
+            if let Paths::Rules(rules) = &paths.rules {
+                if !Path::new(rules).is_file() {
+                    return Err(ErrorKind::Config(format!("{file}: rules file not found: {rules}")).into())
+                }
+            }
+        }
+        Ok(sites)
+    }
+
+    async fn start(sites: Vec<ConfigPath>, client: Client, crawl: bool, resume: Option<&str>) -> Result<Outcome> {
+        let run_id = match resume {
+            Some(id) => String::from(id),
+            None => Today::build().time.get(),
+        };
+        println!("Run id: {run_id}");
+
+        // sites share the reports directory, and so the journal
+        let reports = sites[0].reports.get_path();
+        let mut journaled = match resume {
+            Some(_) => Journal::latest(&reports, &run_id)?,
+            None => Vec::new(),
+        };
+        let mut journal = Journal::open(&reports, &run_id)?;
+
+        // every site is stored under the same timestamp
+        let today = Today::build();
+        let mut built = Vec::new();
+        for paths in sites {
+            let (done, failed): (Vec<Record>, Vec<Record>) = journaled
+                .extract_if(.., |r| r.site == paths.site)
+                .partition(|r| r.error_kind.is_none());
+            let skip: HashSet<String> = done.iter().map(|r| r.target.clone()).collect();
+
+            let isolated_targets = Targets::merge(
+                Targets::merge(
+                    Targets::prep_targets(&paths.targets),
+                    Targets::prep_sitemap(&paths, &client).await
+                ),
+                Some(failed.iter().filter_map(Target::from_record).collect()),
+            )
+            .map(|t| t.into_iter().filter(|t| !skip.contains(&t.to_url())).collect());
+
+            let mut targets = Targets::build(isolated_targets);
+
+            let rules = paths.scan_rules();
+
+            let crawl = if crawl {
+                let mut c = Crawl::build(&paths, &targets);
+                for target in c.resume(&done, &paths.base_url.get_path()) {
+                    targets.enqueue(target);
+                }
+                Some(c)
+            } else {
+                None
+            };
+
+            let mut report = Report::new(&paths, today.clone());
+            for record in done {
+                report.add(record);
+            }
+
+            built.push(pursue_targets(client.clone(), targets, paths, rules, crawl, &mut journal, report).await?);
+        }
+
+        Report::build_all(built)
+    }
+}
+
+fn read_file(path: String) -> Result<BufReader<File>>{
+    let file = File::open(path)?;
+    Ok(BufReader::new(file))
+}
+
+fn create_file_path(root: &Paths, path: &str) -> Result<String> {
+    let mut base_path = root.get_path();
+    if !Path::new(&base_path).is_dir() {
+        create_dir(&base_path)?;
+    }
+
+    for part in path.split('/').filter(|part| !part.is_empty()) {
+        base_path += part;
+        if !Path::new(&base_path).is_dir() {
+            create_dir(&base_path)?;
+        }
+        base_path += "/";
+    }
+
+    Ok(base_path)
+}
+
+// resolves an attribute value against the url of the page it was found on
+fn resolve_link(page: &Url, link: &str) -> Option<Url> {
+    let link = link.trim();
+    if link.is_empty() {
+        return None;
+    }
+
+    match page.join(link) {
+        Ok(mut url) if url.scheme() == "http" || url.scheme() == "https" => {
+            url.set_fragment(None);
+            Some(url)
+        },
+        _ => None,
+    }
+}
+
+// the name a downloaded file is saved under
+fn file_name(url: &Url) -> String {
+    url.path_segments()
+        .and_then(|mut segments| segments.next_back())
+        .and_then(|name| if name.is_empty() { None } else { name.split("?").next() })
+        .map(|name| name.replace("%20", " "))
+        .unwrap_or(String::from("tmp.bin"))
+}
+
+// temporary solution. returns each url downloaded and where it was saved,
+// and why any others were not. one bad link does not stop the rest
+async fn download_files(client: &Client, throttle: &Throttle, names: &SavedNames, scan: HashSet<String>, page: String, path: String) -> (Vec<(Url, String)>, Vec<String>) {
+    let page = match Url::parse(&page) {
+        Ok(page) => page,
+        Err(e) => return (Vec::new(), vec![format!("{page}: {e}")]),
+    };
+    let mut saved = Vec::new();
+    let mut failed = Vec::new();
+
+    // in order, so files sharing a name are told apart the same way every run
+    let mut scan: Vec<String> = scan.into_iter().collect();
+    scan.sort();
+    for target in scan.iter() {
+        let url = match resolve_link(&page, target) {
+            Some(url) => url,
+            _ => continue
+        };
+        match download_file(client, throttle, names, &url, &path).await {
+            Ok(fname) => saved.push((url, fname)),
+            Err(e) => failed.push(format!("{url}: {e}")),
+        }
+    }
+    (saved, failed)
+}
+
+async fn download_file(client: &Client, throttle: &Throttle, names: &SavedNames, url: &Url, path: &str) -> Result<String> {
+    let _permit = throttle.acquire(url).await;
+    let response = client.get(url.clone()).send().await?.error_for_status()?;
+    let fname = names.claim(&(String::from(path) + &file_name(response.url())), url);
+    let content = response.bytes().await?;
+    write_file(content, fname.clone())?;
+    Ok(fname)
+}
+
+// the files saved by a run, each location given to one url so that files
+// sharing a name, like /2019/report.pdf and /2020/report.pdf, are not saved
+// over each other
+#[derive(Default)]
+struct SavedNames {
+    claimed: Mutex<HashMap<String, Url>>,
+}
+
+impl SavedNames {
+    // `location` if no other url has it, otherwise the first free of
+    // `name-2.ext`, `name-3.ext` and so on
+    fn claim(&self, location: &str, url: &Url) -> String {
+        let mut claimed = self.claimed.lock().unwrap();
+        let (stem, extension) = match location.rsplit_once('.') {
+            Some((stem, extension)) if !stem.ends_with('/') && !extension.contains('/') => (stem, String::from(".") + extension),
+            _ => (location, String::new()),
+        };
+
+        let mut candidate = String::from(location);
+        let mut n = 1;
+        while claimed.get(&candidate).is_some_and(|owner| owner != url) {
+            n += 1;
+            candidate = format!("{stem}-{n}{extension}");
+        }
+        claimed.insert(candidate.clone(), url.clone());
+        candidate
+    }
+
+    // a file kept from an earlier run by an unchanged page
+    fn keep(&self, location: &str, url: &Url) {
+        self.claimed.lock().unwrap().entry(String::from(location)).or_insert_with(|| url.clone());
+    }
+}
+
+// async fn download_images(scan: HashSet<Vec<u8>>, path: String) -> Result<()> {
+//     let base_path = "C:/Users/Vel4ta/Desktop/mtc/";
+//     let new_path = &(String::from(base_path) + &path);
+//     if !Path::new(new_path).is_dir() {
+//         create_dir(new_path)?;
+//     }
+//     for item in scan {
+//         let mut url = item.iter().fold(&mut String::new(),|acc, x| {acc.push(*x as char); acc}).to_owned();
+//         if item.iter().next().unwrap() == &b'/' {
+//             url = String::from("https://www.csun.edu") + &url;
 //         }
 //         let copy = url.clone();
 //         let img_name = copy.split("/").last().unwrap().replace("%20", "_");
@@ -500,9 +1891,10 @@ fn write_file(data: Bytes, path: String) -> Result<()> {
     Ok(())
 }
 
-fn a_client_and_runtime() -> Result<(Client, Runtime)> {
+fn a_client_and_runtime(user_agent: &str) -> Result<(Client, Runtime)> {
     let c = Client::builder()
         .timeout(Duration::from_secs(60))
+        .user_agent(user_agent)
         .build()?;
     let r = Builder::new_multi_thread()
         .worker_threads(3)
@@ -511,100 +1903,483 @@ fn a_client_and_runtime() -> Result<(Client, Runtime)> {
     Ok((c, r))
 }
 
-fn pursue_targets(mut targets: Targets, paths: ConfigPath) -> Result<Report> {
-    match a_client_and_runtime() {
-        Ok((client, rt)) => {
-            let (mut dept, mut today) = (paths.departments, Today::build());
+// a target on its way through the fetch, scan, download and store stages
+struct Page {
+    d: Department,
+    record: Record,
+    content: Option<Bytes>,
+    scans: Vec<(String, HashSet<String>)>,
+    // each asset url and the file it is saved in
+    assets: Vec<(Url, String)>,
+    fragment: Option<String>,
+    title: Option<String>,
+    markdown: Option<String>,
+    previous: Option<String>,
+    validators: Validators,
+    started: Instant,
+}
 
-            let mut report = Report::new(
-                Department::build(
-                    Target::build(
-                        &[String::from("reports")]
-                    ),
-                    Today::build(),
-                    paths.reports,
-                )
-            );
-
-            let mut count = 0;
-            while let Some(target) = targets.pop() {
-                let d = Department::build(target, today, dept);
-
-                let handle = rt.spawn(
-                    collect_content(
-                        client.request(
-                            Method::GET,
-                            paths.base_url.make_path(
-                                d.path.to_url()
-                            )
-                        )
-                    )
-                );
-                
-                count += 1;
-                if count%3 == 0 {
-                    count -= count;
-                    std::thread::sleep(Duration::from_millis(1000));
+async fn pursue_targets(client: Client, mut targets: Targets, paths: ConfigPath, rules: ScanRules, mut crawl: Option<Crawl>, journal: &mut Journal, mut report: Report) -> Result<Report> {
+    let robots = match robots::fetch(&client, &paths.base_url.get_path(), &paths.user_agent()).await {
+        Ok(r) => r,
+        Err(e) => {
+            println!("robots.txt: {e}");
+            Robots::allow_all()
+        },
+    };
+
+    let policy = paths.retry_policy();
+
+    let concurrency = paths.concurrency.get_number(3).max(1);
+    let throttle = Throttle::new(concurrency, paths.rate_limit.get_number(3), robots.crawl_delay);
+    let names = SavedNames::default();
+
+    let today = report.info.today.clone();
+    let mut links = paths.check_links.then(Links::default);
+
+    // crawled pages are pursued a depth at a time
+    while !targets.is_empty() {
+        let wave: Vec<Department> = std::iter::from_fn(|| targets.pop())
+            .map(|target| Department::build(target, today.clone(), paths.departments.clone()))
+            .collect();
+        let mut discovered = Vec::new();
+
+        {
+            let mut records = stream::iter(wave)
+                .map(|d| fetch_page(d, &client, &robots, &throttle, &policy, &paths))
+                .buffer_unordered(concurrency)
+                .map(|page| scan_page(page, &rules, crawl.as_mut(), links.as_mut(), &mut discovered, &paths))
+                .map(|page| download_assets(page, &client, &throttle, &names, &paths))
+                .buffer_unordered(concurrency)
+                .map(|page| rewrite_page(page, &paths))
+                .map(store_page);
+
+            while let Some(record) = records.next().await {
+                if let Err(e) = journal.append(&record) {
+                    println!("journal: {e}");
                 }
+                if paths.verbose {
+                    println!("{} {} ({}ms)", record.status.map(|s| s.to_string()).unwrap_or_else(|| String::from("---")), record.url, record.duration_ms);
+                }
+                report.add(record);
+            }
+        }
 
-                match d.create_path() {
-                    Ok(_) => match rt.block_on(handle) {
-                        Ok(Some(content)) => {
-
-                            // temporary solution
-                            let copy = content.clone();
-                            let scan = proper_scan_bytes(
-                                copy,
-                                "id=\"content\"",
-                                "class=\"layout-csun--footer\"",
-                                "<a ",
-                                "href",
-                                "/sites/default/files/",
-                                "\""
-                            );
+        for target in discovered {
+            targets.enqueue(target);
+        }
+    }
 
-                            let file_handle = rt.spawn(download_files(scan, d.path.to_url()));
-                            if let Err(e) = rt.block_on(file_handle) {
-                                println!("{e}");
-                            }
+    if let Some(c) = crawl {
+        report.attach("targets.jsonl", c.to_targets_file()?);
+    }
 
+    if let Some(links) = links {
+        let checked = links.check(&throttle, &paths.user_agent(), &paths.base_url.get_path(), concurrency).await;
+        println!("Links checked: {}, broken: {}", checked.len(), checked.iter().filter(|l| l.broken()).count());
+        for (department, broken) in links::broken_by_department(&checked) {
+            report.attach(&format!("broken-links-{department}.csv"), Bytes::from(broken));
+        }
+        report.attach("links.json", Bytes::from(serde_json::to_vec_pretty(&checked)?));
+    }
 
-                            report.add(d.store(content));
-                        },
-                        Ok(None) => println!("{}", d.path.to_url()),
-                        Err(e) => println!("{e}"),
-                    },
-                    Err(e) => println!("{e}"),
-                };
+    Ok(report)
+}
 
-                (dept, _, today) = d.destroy();
-            }
-            Ok(report)
-        },  
-        Err(e) => Err(e),
+async fn fetch_page(d: Department, client: &Client, robots: &Robots, throttle: &Throttle, policy: &RetryPolicy, paths: &ConfigPath) -> Page {
+    let url = paths.base_url.make_path(d.path.to_url());
+    let mut record = Record::new(&paths.site, &d.path.to_url(), &url);
+    record.title = d.path.label.title.clone();
+    record.owner = d.path.label.owner.clone();
+    record.entry = Some(d.path.to_entry());
+    let mut page = Page {
+        d,
+        record,
+        content: None,
+        scans: Vec::new(),
+        assets: Vec::new(),
+        fragment: None,
+        title: None,
+        markdown: None,
+        previous: None,
+        validators: Validators::default(),
+        started: Instant::now(),
+    };
+
+    if !robots_allow(robots, &url) {
+        page.record.fail(Failure::Disallowed, String::from("disallowed by robots.txt"));
+        return page;
+    }
+
+    page.previous = page.d.previous_copy();
+    let validators = page.previous
+        .as_deref()
+        .map(Validators::read)
+        .unwrap_or_default();
+
+    let fetched = collect_content(client, &url, &validators, policy, throttle, paths.verbose).await;
+    page.record.status = fetched.status;
+    page.record.final_url = fetched.final_url.clone();
+    page.record.content_type = fetched.content_type.clone();
+    page.record.retries = fetched.retries();
+
+    match fetched.content {
+        Some(content) => {
+            page.record.bytes = content.len();
+            page.content = Some(content);
+            page.validators = fetched.validators;
+        },
+        None if fetched.not_modified => page.record.unchanged = true,
+        None => match fetched.status {
+            Some(_) => page.record.fail(Failure::Status, fetched.describe()),
+            None => page.record.fail(Failure::Network, fetched.describe()),
+        },
     }
+    page
 }
 
-async fn collect_content(request: RequestBuilder) -> Option<Bytes> {
-    match request.send().await {
-        Ok(r) if r.status().is_success() => {
-            match r.bytes().await {
-                Ok(b) => Some(b),
-                Err(e) => {
-                    println!("{e}");
-                    None
-                },
+// finds what every scan rule wants downloaded, and any pages worth crawling
+fn scan_page(mut page: Page, rules: &ScanRules, crawl: Option<&mut Crawl>, links: Option<&mut Links>, discovered: &mut Vec<Target>, paths: &ConfigPath) -> Page {
+    let region = page.d.path.label.region(&rules.region);
+    let region = region.as_ref().unwrap_or(&rules.region);
+
+    if let Some(content) = &page.content {
+        // a link belongs to the first rule that finds it, so rules whose
+        // patterns overlap do not download one file into two trees
+        let mut claimed = HashSet::new();
+        page.scans = rules.rules
+            .iter()
+            .map(|rule| {
+                let found = rule.scan(content, region)
+                    .into_iter()
+                    .filter(|link| claimed.insert(link.clone()))
+                    .collect();
+                (rule.to_path(&page.d.path), found)
+            })
+            .collect();
+    }
+
+    // an unchanged page gets its fragment and crawled links from the previous
+    // copy, so every capture has one and a crawl still reaches past it
+    let raw = match (&page.content, page.record.unchanged, &page.previous) {
+        (Some(content), _, _) => Some(content.to_vec()),
+        (None, true, Some(previous)) => std::fs::read(previous).ok(),
+        _ => None,
+    };
+    if let Some(raw) = raw {
+        if let Some(c) = crawl {
+            discovered.extend(c.discover(&raw, &page.d.path, &paths.base_url.get_path()));
+        }
+        // nothing is downloaded for an unchanged page, so its links point
+        // at whatever an earlier run saved
+        if let (None, Some(previous)) = (&page.content, &page.previous) {
+            page.assets = read_saved_files(previous);
+        }
+        if let (Some(links), Ok(url)) = (links, Url::parse(&page.record.url)) {
+            let source = Source { department: page.d.path.base.clone(), page: page.record.url.clone() };
+            links.scan(&raw, region, &url, source);
+        }
+        page.fragment = Some(html::clean_fragment(&raw, Some(region)));
+        page.title = page.d.path.label.title.clone().or_else(|| html::page_title(&raw));
+    }
+    page
+}
+
+async fn download_assets(mut page: Page, client: &Client, throttle: &Throttle, names: &SavedNames, paths: &ConfigPath) -> Page {
+    for (url, file) in page.assets.iter() {
+        names.keep(file, url);
+    }
+
+    let mut failures = Vec::new();
+    for (path, scan) in std::mem::take(&mut page.scans) {
+        let (saved, failed) = match create_file_path(&paths.files, &path) {
+            Ok(file_path) => download_files(
+                client,
+                throttle,
+                names,
+                scan,
+                paths.base_url.make_path(page.d.path.to_url()),
+                file_path
+            ).await,
+            Err(e) => (Vec::new(), vec![e.to_string()]),
+        };
+
+        if paths.verbose {
+            for (url, file) in saved.iter() {
+                println!("{url} -> {file}");
             }
+        }
+        page.record.assets += saved.len();
+        page.assets.extend(saved);
+        failures.extend(failed);
+    }
+
+    if !failures.is_empty() {
+        page.record.fail(Failure::Asset, failures.join("; "));
+    }
+    page
+}
+
+// points the links of the cleaned content at where things live after the
+// migration: downloaded assets at their files, internal pages at their slugs,
+// both as root-relative paths. the raw copy is left as fetched
+fn rewrite_page(mut page: Page, paths: &ConfigPath) -> Page {
+    let Some(fragment) = &page.fragment else {
+        return page;
+    };
+    let Ok(url) = Url::parse(&paths.base_url.make_path(page.d.path.to_url())) else {
+        return page;
+    };
+    let base_url = paths.base_url.get_path();
+
+    let mut rewrites: Vec<Rewrite> = Vec::new();
+    let fragment = html::rewrite_links(fragment, |link| {
+        let resolved = resolve_link(&url, link)?;
+        let (kind, to) = match page.assets.iter().find(|(asset, _)| asset == &resolved) {
+            Some((_, file)) => (LinkKind::Asset, asset_path(file, paths)),
+            // a page with a query has no slug of its own
+            None if is_page(&resolved) && resolved.query().is_none() => {
+                let slug = Target::from_url(&resolved, &base_url)?.slug();
+                match url.join(link).ok()?.fragment() {
+                    Some(anchor) => (LinkKind::Page, slug + "#" + anchor),
+                    None => (LinkKind::Page, slug),
+                }
+            },
+            None => return None,
+        };
+
+        if !rewrites.iter().any(|r| r.from == link) {
+            rewrites.push(Rewrite { kind, from: String::from(link), to: to.clone() });
+        }
+        Some(to)
+    });
+
+    if paths.markdown {
+        let title = page.title.clone().unwrap_or_default();
+        page.markdown = Some(page.d.front_matter(&title, &page.record.url) + &markdown::from_html(&fragment));
+    }
+    page.fragment = Some(fragment);
+    page.record.rewrites = rewrites;
+    page
+}
+
+// where a downloaded file is served on the new site, which serves the Files
+// root as its own. the path is root-relative, so it holds from any page
+fn asset_path(file: &str, paths: &ConfigPath) -> String {
+    let files = paths.files.get_path();
+    String::from("/") + &file.strip_prefix(&files).unwrap_or(file).replace(' ', "%20")
+}
+
+// the collisions among a site's redirects, named after the site when a run has several
+fn site_collisions(redirects: &[Redirect], paths: &ConfigPath) -> Vec<String> {
+    redirects::collisions(redirects).into_iter().map(|c| match paths.site.is_empty() {
+        true => c,
+        false => paths.site.clone() + ": " + &c,
+    }).collect()
+}
+
+// a report for each site from the journal of an earlier run
+fn journaled_reports(sites: &[ConfigPath], run_id: &str, name: &str) -> Result<Vec<Report>> {
+    let today = Today::build();
+    let mut records = Journal::latest(&sites[0].reports.get_path(), run_id)?;
+
+    let mut reports = Vec::new();
+    for paths in sites.iter() {
+        let mut report = Report::named(paths, today.clone(), name);
+        for record in records.extract_if(.., |r| r.site == paths.site) {
+            report.add(record);
+        }
+        reports.push(report);
+    }
+    Ok(reports)
+}
+
+// every page stored and file saved by a run, from where it was to where it
+// is now. the old server can only redirect urls on its own host
+fn redirects_of(records: &[Record], paths: &ConfigPath) -> Vec<Redirect> {
+    let host = Url::parse(&paths.base_url.get_path()).ok().and_then(|u| u.host_str().map(String::from));
+
+    let pages = records
+        .iter()
+        // a page missing one of its files is still live at its slug
+        .filter(|r| r.stored.is_some() && r.error_kind != Some(Failure::Storage))
+        .filter_map(|r| Some(Redirect {
+            from: Url::parse(&r.url).ok()?,
+            to: Target::parse(&r.target)?.slug(),
+            kind: LinkKind::Page,
+        }));
+    let files = records
+        .iter()
+        .flat_map(|r| r.files.iter())
+        .filter_map(|f| Some(Redirect {
+            from: Url::parse(&f.url).ok()?,
+            to: asset_path(&f.file, paths),
+            kind: LinkKind::Asset,
+        }));
+
+    let mut redirects: Vec<Redirect> = pages
+        .chain(files)
+        .filter(|r| r.from.host_str().map(String::from) == host)
+        .collect();
+    // a file shared by several pages is saved once for each
+    redirects.sort_by(|a, b| a.from.as_str().cmp(b.from.as_str()));
+    redirects.dedup_by(|a, b| a.from == b.from);
+    redirects
+}
+
+fn store_page(mut page: Page) -> Record {
+    if let Some(content) = page.content {
+        let stored = page.d.create_path()
+            .and_then(|_| page.d.store(content))
+            .and_then(|location| page.validators.write(&location).map(|_| location))
+            .and_then(|location| write_saved_files(&location, &page.assets).map(|_| location));
+        match stored {
+            Ok(location) => page.record.stored = Some(location),
+            Err(e) => page.record.fail(Failure::Storage, e.to_string()),
+        }
+    } else if let (true, Some(previous)) = (page.record.unchanged, page.previous) {
+        match page.d.create_path().and_then(|_| page.d.store_reference(&previous)) {
+            Ok(_) => page.record.stored = Some(previous),
+            Err(e) => page.record.fail(Failure::Storage, e.to_string()),
+        }
+    }
+
+    if let (Some(fragment), Some(_)) = (&page.fragment, &page.record.stored) {
+        if let Err(e) = page.d.store_content(fragment) {
+            page.record.fail(Failure::Storage, e.to_string());
+        }
+    }
+    if let (Some(document), Some(_)) = (&page.markdown, &page.record.stored) {
+        if let Err(e) = page.d.store_markdown(document) {
+            page.record.fail(Failure::Storage, e.to_string());
+        }
+    }
+
+    page.record.files = page.assets
+        .iter()
+        .map(|(url, file)| SavedFile { url: String::from(url.as_str()), file: file.clone() })
+        .collect();
+    page.record.duration_ms = page.started.elapsed().as_millis();
+    page.record
+}
+
+fn robots_allow(robots: &Robots, url: &str) -> bool {
+    match Url::parse(url) {
+        Ok(u) => match u.query() {
+            Some(q) => robots.allowed(&(String::from(u.path()) + "?" + q)),
+            None => robots.allowed(u.path()),
         },
-        Ok(r) => {
-            println!("{}", r.status());
-            None
-        },
+        Err(_) => true,
+    }
+}
+
+// the content of a page, and every attempt it took to get it
+struct Fetched {
+    content: Option<Bytes>,
+    status: Option<u16>,
+    final_url: Option<String>,
+    content_type: Option<String>,
+    validators: Validators,
+    not_modified: bool,
+    attempts: Vec<Attempt>,
+}
+
+impl Fetched {
+    fn retries(&self) -> usize {
+        self.attempts.len().saturating_sub(1)
+    }
+
+    fn describe(&self) -> String {
+        self.attempts
+            .iter()
+            .map(Attempt::describe)
+            .collect::<Vec<String>>()
+            .join(" ")
+    }
+}
+
+async fn collect_content(client: &Client, url: &str, validators: &Validators, policy: &RetryPolicy, throttle: &Throttle, verbose: bool) -> Fetched {
+    let mut fetched = Fetched {
+        content: None,
+        status: None,
+        final_url: None,
+        content_type: None,
+        validators: Validators::default(),
+        not_modified: false,
+        attempts: Vec::new(),
+    };
+
+    let url = match Url::parse(url) {
+        Ok(url) => url,
         Err(e) => {
-            println!("{e}");
-            None
+            fetched.attempts.push(Attempt { status: None, error: Some(e.to_string()), waited: Duration::ZERO });
+            return fetched;
         },
+    };
+
+    loop {
+        let permit = throttle.acquire(&url).await;
+        let response = validators.apply(client.request(Method::GET, url.clone())).send().await;
+        if let Ok(r) = &response {
+            fetched.status = Some(r.status().as_u16());
+            fetched.final_url = Some(r.url().to_string());
+            fetched.content_type = r.headers()
+                .get(CONTENT_TYPE)
+                .and_then(|v| v.to_str().ok())
+                .map(String::from);
+        }
+
+        let (status, error, headers, retry) = match response {
+            Ok(r) if r.status() == StatusCode::NOT_MODIFIED => {
+                fetched.attempts.push(Attempt { status: Some(304), error: None, waited: Duration::ZERO });
+                fetched.not_modified = true;
+                return fetched;
+            },
+            Ok(r) if r.status().is_success() => {
+                let status = r.status().as_u16();
+                fetched.validators = Validators::from_headers(r.headers());
+                match r.bytes().await {
+                    Ok(b) => {
+                        fetched.attempts.push(Attempt { status: Some(status), error: None, waited: Duration::ZERO });
+                        fetched.content = Some(b);
+                        return fetched;
+                    },
+                    Err(e) => (Some(status), Some(e.to_string()), None, true),
+                }
+            },
+            Ok(r) => (Some(r.status().as_u16()), None, Some(r.headers().clone()), policy.retryable(r.status())),
+            Err(e) => (None, Some(e.to_string()), None, retry::can_succeed(&e)),
+        };
+
+        let (mut again, mut error) = (retry && fetched.attempts.len() + 1 < policy.attempts, error);
+        let waited = match again {
+            true => match policy.wait(fetched.attempts.len() + 1, headers.as_ref()) {
+                Ok(waited) => waited,
+                // a server asking for a longer wait than RetryMaxDelay is given up on
+                Err(asked) => {
+                    again = false;
+                    error = Some(format!("Retry-After of {}s is longer than RetryMaxDelay", asked.as_secs()));
+                    Duration::ZERO
+                },
+            },
+            false => Duration::ZERO,
+        };
+
+        drop(permit);
+
+        let attempt = Attempt { status, error, waited };
+        if verbose {
+            println!("{url}: {}", attempt.describe());
+        }
+        fetched.attempts.push(attempt);
+
+        if !again {
+            break;
+        }
+        tokio::time::sleep(waited).await;
     }
+
+    fetched
 }
 
 fn join(s: &[String], acc: String) -> String {
@@ -688,4 +2463,284 @@ where
 //             None
 //         },
 //     }
-// }
\ No newline at end of file
+// }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // a page that answered 304, with `html` as the copy stored by an earlier run
+    fn unchanged_page(name: &str, html: &str) -> Page {
+        let previous = std::env::temp_dir().join(format!("web_migration-{}-{name}.txt", std::process::id()));
+        std::fs::write(&previous, html).unwrap();
+
+        let target = Target::new("dept", "");
+        let mut record = Record::new("", &target.to_url(), "https://a.edu/as/dept/");
+        record.status = Some(304);
+        record.unchanged = true;
+        Page {
+            d: Department::build(target, Today::build(), Paths::Departments(String::from("unused/"))),
+            record,
+            content: None,
+            scans: Vec::new(),
+            assets: Vec::new(),
+            fragment: None,
+            title: None,
+            markdown: None,
+            previous: Some(previous.to_string_lossy().into_owned()),
+            validators: Validators::default(),
+            started: Instant::now(),
+        }
+    }
+
+    #[test]
+    fn crawls_past_an_unchanged_page() {
+        let paths = ConfigPath::build(vec![Paths::BaseUrl(String::from("https://a.edu/as/"))]);
+        let rules = ScanRules::build(None);
+        let mut crawl = Crawl::build(&paths, &Targets::build(Some(vec![Target::new("dept", "")])));
+        let mut discovered = Vec::new();
+
+        let html = r#"<div id="content"><a href="child.html">c</a><a href="/as/other/">o</a><a href="https://b.org/">x</a></div>"#;
+        let page = scan_page(unchanged_page("crawl", html), &rules, Some(&mut crawl), None, &mut discovered, &paths);
+        let _ = std::fs::remove_file(page.previous.unwrap());
+
+        let found: Vec<String> = discovered.iter().map(Target::to_line).collect();
+        assert_eq!(found, ["other", "dept/child.html"]);
+        assert!(discovered.iter().all(|t| t.depth == 1));
+    }
+
+    #[test]
+    fn discovered_targets_read_back_the_same() {
+        let paths = ConfigPath::build(vec![Paths::BaseUrl(String::from("https://a.edu/as/"))]);
+        let mut crawl = Crawl::build(&paths, &Targets::build(Some(vec![Target::new("dept", "")])));
+        let html = br#"<a href="child.html">c</a><a href="sub/">s</a>"#;
+        crawl.discover(html, &Target::new("dept", ""), "https://a.edu/as/");
+
+        let file = String::from_utf8(crawl.to_targets_file().unwrap().to_vec()).unwrap();
+        let read: Vec<Target> = listing::read_jsonl(&file, "targets.jsonl").into_iter().filter_map(Target::from_entry).collect();
+        let urls: Vec<String> = read.iter().map(Target::to_url).collect();
+        assert_eq!(urls, ["dept/", "dept/child.html", "dept/sub/"]);
+        assert_eq!(read[1].to_store(), "child.html.txt");
+    }
+
+    #[test]
+    fn resumed_crawls_skip_done_pages_but_follow_their_links() {
+        let paths = ConfigPath::build(vec![Paths::BaseUrl(String::from("https://a.edu/as/"))]);
+        let stored = std::env::temp_dir().join(format!("web_migration-{}-resume.txt", std::process::id()));
+        std::fs::write(&stored, r#"<a href="/as/other/">o</a><a href="/as/dept/child.html">c</a>"#).unwrap();
+
+        let mut seed = Record::new("", "dept/", "https://a.edu/as/dept/");
+        seed.stored = Some(stored.to_string_lossy().into_owned());
+        let other = Record::new("", "other/", "https://a.edu/as/other/");
+
+        let mut crawl = Crawl::build(&paths, &Targets::build(None));
+        let found: Vec<String> = crawl.resume(&[seed, other], "https://a.edu/as/").iter().map(Target::to_url).collect();
+        let _ = std::fs::remove_file(stored);
+        assert_eq!(found, ["dept/child.html"]);
+        assert_eq!(crawl.found.len(), 3);
+    }
+
+    #[test]
+    fn files_sharing_a_name_are_saved_apart() {
+        let names = SavedNames::default();
+        let url = |u: &str| Url::parse(u).unwrap();
+        let (first, second) = (url("https://a.edu/files/2019/report.pdf"), url("https://a.edu/files/2020/report.pdf"));
+
+        assert_eq!(names.claim("W/files/dept/report.pdf", &first), "W/files/dept/report.pdf");
+        assert_eq!(names.claim("W/files/dept/report.pdf", &second), "W/files/dept/report-2.pdf");
+        assert_eq!(names.claim("W/files/dept/report.pdf", &first), "W/files/dept/report.pdf");
+        assert_eq!(names.claim("W/files/dept/report.pdf", &url("https://a.edu/report.pdf")), "W/files/dept/report-3.pdf");
+        assert_eq!(names.claim("W/files/dept/report.pdf", &second), "W/files/dept/report-2.pdf");
+
+        assert_eq!(names.claim("W/files/v1.2/README", &first), "W/files/v1.2/README");
+        assert_eq!(names.claim("W/files/v1.2/README", &second), "W/files/v1.2/README-2");
+
+        names.keep("W/files/dept/map.png", &first);
+        assert_eq!(names.claim("W/files/dept/map.png", &second), "W/files/dept/map-2.png");
+    }
+
+    #[test]
+    fn the_shipped_rules_find_stylesheets_in_the_head() {
+        let rules = ScanRules::build(ScanRules::prep_rules(&Paths::Rules(String::from("config/rules.txt"))));
+        let html = br#"<html><head><link rel="stylesheet" href="/themes/site.css"></head><body>
+            <a href="/outside.pdf">x</a>
+            <div id="content"><link href="/inline.css"><a href="/docs/a.pdf">a</a><a href="/sites/default/files/b.pdf">b</a></div>
+            <footer class="layout-csun--footer"></footer></body></html>"#;
+
+        let found: Vec<(String, Vec<String>)> = rules.rules
+            .iter()
+            .map(|rule| {
+                let mut links: Vec<String> = rule.scan(html, &rules.region).into_iter().collect();
+                links.sort();
+                (rule.tag.clone() + " " + &rule.pattern, links)
+            })
+            .filter(|(_, links)| !links.is_empty())
+            .collect();
+        assert_eq!(found, [
+            (String::from("a /sites/default/files/"), vec![String::from("/sites/default/files/b.pdf")]),
+            (String::from("link .css"), vec![String::from("/inline.css"), String::from("/themes/site.css")]),
+            (String::from("a .pdf"), vec![String::from("/docs/a.pdf"), String::from("/sites/default/files/b.pdf")]),
+        ]);
+    }
+
+    fn sites(toml: &str) -> std::result::Result<Vec<ConfigPath>, String> {
+        let output = "[output]\ndepartments = \"W/departments\"\nreports = \"W/reports/\"\nfiles = \"W/files\"\n";
+        ConfigPath::from_settings(Settings::parse(&(String::from(toml) + output))?, "/base/")
+    }
+
+    #[test]
+    fn sites_inherit_the_top_level_and_keep_their_own_directories() {
+        let sites = sites(r#"
+            [http]
+            user_agent = "bot"
+            concurrency = 2
+            [scan]
+            content_start = "id=\"main\""
+            [[scan.rules]]
+            tag = "a"
+            attribute = "href"
+            pattern = ".pdf"
+            [[sites]]
+            name = "as"
+            base_url = "https://a.edu/as/"
+            targets = "as.txt"
+            [[sites]]
+            name = "aid"
+            base_url = "https://a.edu/financial-aid"
+            targets = "/lists/aid.txt"
+            departments = "elsewhere"
+            files = "aid-files/"
+            [sites.scan]
+            content_end = "id=\"end\""
+        "#).unwrap();
+
+        let [a, b] = &sites[..] else { panic!("two sites") };
+        assert_eq!((a.site.as_str(), b.site.as_str()), ("as", "aid"));
+        assert_eq!((a.user_agent(), b.concurrency.get_number(0)), (String::from("bot"), 2));
+        assert_eq!(b.base_url.get_path(), "https://a.edu/financial-aid/");
+        assert_eq!((a.targets.get_path(), b.targets.get_path()), (String::from("/base/as.txt"), String::from("/lists/aid.txt")));
+        assert_eq!(a.departments.get_path(), "/base/W/departments/as/");
+        assert_eq!(b.departments.get_path(), "/base/elsewhere/");
+        assert_eq!(a.files.get_path(), "/base/W/files/as/");
+        assert_eq!(b.files.get_path(), "/base/aid-files/");
+
+        // a site's scan section replaces the top level one
+        assert_eq!(a.scan.len(), 2);
+        assert!(matches!(&b.scan[..], [ScanRule::ContentEnd(e)] if e == "id=\"end\""));
+    }
+
+    #[test]
+    fn refuses_bad_site_lists() {
+        let site = |name: &str| format!("[[sites]]\nname = \"{name}\"\nbase_url = \"https://a.edu/\"\ntargets = \"t.txt\"\n");
+        assert_eq!(sites(&(site("as") + &site("as"))).err().as_deref(), Some("duplicate site `as`"));
+        assert_eq!(sites(&site("")).err().as_deref(), Some("site name `` must be a non-empty directory name"));
+        assert_eq!(sites(&site("a/b")).err().as_deref(), Some("site name `a/b` must be a non-empty directory name"));
+        assert_eq!(sites("").err().as_deref(), Some("missing `targets` in [paths]\nmissing `base_url` in [http]"));
+    }
+
+    #[test]
+    fn targets_pop_the_highest_priority_first() {
+        let entries = listing::read_csv("path,priority\nlow/,-1\nplain/,\nhigh/,9\nmid/,3\n", "targets.csv");
+        let mut targets = Targets::build(Some(entries.into_iter().filter_map(Target::from_entry).collect()));
+        let order: Vec<String> = std::iter::from_fn(|| targets.pop()).map(|t| t.to_url()).collect();
+        assert_eq!(order, ["high/", "mid/", "plain/", "low/"]);
+    }
+
+    fn slug(line: &str) -> String {
+        Target::parse(line).map(|t| t.slug()).unwrap_or_default()
+    }
+
+    #[test]
+    fn resolves_links_against_the_page() {
+        let page = Url::parse("https://a.edu/as/dept/people.html").unwrap();
+        let resolved = |link: &str| resolve_link(&page, link).map(|u| String::from(u.as_str()));
+        assert_eq!(resolved("/x").as_deref(), Some("https://a.edu/x"));
+        assert_eq!(resolved("x").as_deref(), Some("https://a.edu/as/dept/x"));
+        assert_eq!(resolved("../x").as_deref(), Some("https://a.edu/as/x"));
+        assert_eq!(resolved("//b.org/x").as_deref(), Some("https://b.org/x"));
+        assert_eq!(resolved(" x?a=1&b=2 ").as_deref(), Some("https://a.edu/as/dept/x?a=1&b=2"));
+        assert_eq!(resolved("x#part").as_deref(), Some("https://a.edu/as/dept/x"));
+        assert_eq!(resolved("http://b.org/").as_deref(), Some("http://b.org/"));
+        assert_eq!(resolved("#top").as_deref(), Some("https://a.edu/as/dept/people.html"));
+        for link in ["", "  ", "mailto:a@a.edu", "tel:555", "javascript:void(0)", "data:image/png;base64,AA"] {
+            assert_eq!(resolved(link), None, "for {link}");
+        }
+    }
+
+    #[test]
+    fn slugs_drop_page_extensions_and_index_pages() {
+        assert_eq!(slug("dept/page.html"), "/dept/page");
+        assert_eq!(slug("dept/sub/page.aspx"), "/dept/sub/page");
+        assert_eq!(slug("dept/report.pdf"), "/dept/report.pdf");
+        assert_eq!(slug("dept/index.html"), "/dept");
+        assert_eq!(slug("dept/sub/index.php"), "/dept/sub");
+        assert_eq!(slug("index.html"), "/");
+        assert_eq!(slug("dept/reindex.html"), "/dept/reindex");
+        assert_eq!(slug("dept/appendix-index"), "/dept/appendix-index");
+        assert_eq!(slug("dept/"), "/dept");
+        assert_eq!(slug("dept/sub/"), "/dept/sub");
+    }
+
+    #[test]
+    fn failed_targets_are_retried_with_their_labels() {
+        let entry = listing::Entry {
+            path: String::from("dept/people.html"),
+            title: Some(String::from("People")),
+            priority: Some(5),
+            content_end: Some(String::from("div.end")),
+            ..Default::default()
+        };
+        let target = Target::from_entry(entry).unwrap();
+        let again = Target::from_entry(target.to_entry()).unwrap();
+        assert_eq!(again.to_url(), "dept/people.html");
+        assert_eq!(again.label.title.as_deref(), Some("People"));
+        assert_eq!(again.label.priority, 5);
+        assert_eq!(again.label.content_end.as_deref(), Some("div.end"));
+    }
+
+    #[test]
+    fn redirects_pages_whose_files_failed() {
+        let paths = ConfigPath::build(vec![
+            Paths::BaseUrl(String::from("https://a.edu/as/")),
+            Paths::Files(String::from("W/files/")),
+        ]);
+        let mut page = Record::new("", "dept/", "https://a.edu/as/dept/");
+        page.stored = Some(String::from("W/departments/dept/1/.txt"));
+        page.fail(Failure::Asset, String::from("https://a.edu/gone.pdf: 404"));
+        page.files = vec![SavedFile { url: String::from("https://a.edu/as/a.pdf"), file: String::from("W/files/dept/a.pdf") }];
+        let mut lost = Record::new("", "other/", "https://a.edu/as/other/");
+        lost.stored = Some(String::from("W/departments/other/1/.txt"));
+        lost.fail(Failure::Storage, String::from("disk full"));
+
+        let redirects: Vec<(String, String)> = redirects_of(&[page, lost], &paths)
+            .into_iter()
+            .map(|r| (String::from(r.from.as_str()), r.to))
+            .collect();
+        assert_eq!(redirects, [
+            (String::from("https://a.edu/as/a.pdf"), String::from("/dept/a.pdf")),
+            (String::from("https://a.edu/as/dept/"), String::from("/dept")),
+        ]);
+    }
+
+    #[test]
+    fn converted_configs_read_back_the_same() {
+        let base = std::env::temp_dir().join(format!("web_migration-{}-convert/", std::process::id()));
+        let _ = std::fs::remove_dir_all(&base);
+        std::fs::create_dir_all(base.join("config")).unwrap();
+        for file in ["config.txt", "rules.txt"] {
+            std::fs::copy(Path::new("config").join(file), base.join("config").join(file)).unwrap();
+        }
+        let base = base.to_str().unwrap();
+
+        let written = Manager::convert(base, None).unwrap();
+        let legacy = Manager::configure(base, Some(&(String::from(base) + "config/config.txt")), &Options::default()).unwrap().remove(0);
+        let converted = Manager::configure(base, Some(&written), &Options::default()).unwrap().remove(0);
+
+        assert_eq!(converted.departments.get_path(), legacy.departments.get_path());
+        assert_eq!(converted.base_url.get_path(), "https://www.csun.edu/as/");
+        assert_eq!(converted.retry_max_delay.get_number(0), 60000);
+        assert_eq!(converted.scan_rules_prepped().len(), 7);
+        assert_eq!(converted.to_settings(base).to_toml(), legacy.to_settings(base).to_toml());
+        std::fs::remove_dir_all(base).unwrap();
+    }
+}
@@ -0,0 +1,247 @@
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+use futures::stream::{self, StreamExt};
+use reqwest::header::LOCATION;
+use reqwest::redirect::Policy;
+use reqwest::{Client, Method, Url};
+use serde::Serialize;
+
+use crate::html::{self, Region};
+use crate::record::csv_field;
+use crate::throttle::Throttle;
+use crate::resolve_link;
+
+// the attributes of page content that point somewhere
+const LINKS: [(&str, &str); 6] = [
+    ("a", "href"), ("img", "src"), ("img", "srcset"), ("source", "src"), ("source", "srcset"), ("iframe", "src"),
+];
+
+// hops followed before a chain is reported as a loop
+const MAX_REDIRECTS: usize = 10;
+
+// a page a link was found on
+#[derive(Serialize, Clone, PartialEq)]
+pub struct Source {
+    pub department: String,
+    pub page: String,
+}
+
+// what checking one link found
+#[derive(Serialize)]
+pub struct Link {
+    pub url: String,
+    // on the same scheme, host and port as the site
+    pub internal: bool,
+    pub status: Option<u16>,
+    // every url redirected to, in order
+    pub redirects: Vec<String>,
+    pub error: Option<String>,
+    pub sources: Vec<Source>,
+}
+
+impl Link {
+    pub fn broken(&self) -> bool {
+        failed(self.status, &self.error)
+    }
+}
+
+fn failed(status: Option<u16>, error: &Option<String>) -> bool {
+    error.is_some() || status.is_none_or(|s| s >= 400)
+}
+
+// every link found in the content of a run's pages, and the pages it was on
+#[derive(Default)]
+pub struct Links {
+    found: BTreeMap<Url, Vec<Source>>,
+}
+
+impl Links {
+    pub fn scan(&mut self, data: &[u8], region: &Region, page: &Url, source: Source) {
+        for (tag, attribute) in LINKS {
+            for link in html::extract_attributes(data, Some(region), tag, attribute, "") {
+                let Some(url) = resolve_link(page, &link) else {
+                    continue;
+                };
+                let sources = self.found.entry(url).or_default();
+                if !sources.contains(&source) {
+                    sources.push(source.clone());
+                }
+            }
+        }
+    }
+
+    // each link once, however many pages it is on. redirects are followed by
+    // hand so the chain can be reported. a client that cannot be built fails
+    // every link rather than the run
+    pub async fn check(self, throttle: &Throttle, user_agent: &str, base_url: &str, concurrency: usize) -> Vec<Link> {
+        let client = Client::builder()
+            .timeout(Duration::from_secs(30))
+            .user_agent(user_agent)
+            .redirect(Policy::none())
+            .build()
+            .map_err(|e| e.to_string());
+        let origin = Url::parse(base_url).ok().map(|u| u.origin());
+
+        let mut links = stream::iter(self.found)
+            .map(|(url, sources)| {
+                let (client, origin) = (&client, &origin);
+                async move {
+                    let checked = match client {
+                        Ok(client) => check(client, throttle, &url).await,
+                        Err(e) => Checked { status: None, redirects: Vec::new(), error: Some(e.clone()) },
+                    };
+                    Link {
+                        internal: Some(url.origin()) == *origin,
+                        url: String::from(url.as_str()),
+                        status: checked.status,
+                        redirects: checked.redirects,
+                        error: checked.error,
+                        sources,
+                    }
+                }
+            })
+            .buffer_unordered(concurrency)
+            .collect::<Vec<Link>>()
+            .await;
+        links.sort_by(|a, b| a.url.cmp(&b.url));
+        links
+    }
+}
+
+struct Checked {
+    status: Option<u16>,
+    redirects: Vec<String>,
+    error: Option<String>,
+}
+
+// some servers refuse or mishandle HEAD, so any failure is tried again with GET
+async fn check(client: &Client, throttle: &Throttle, url: &Url) -> Checked {
+    let checked = follow(client, throttle, Method::HEAD, url).await;
+    match failed(checked.status, &checked.error) {
+        true => follow(client, throttle, Method::GET, url).await,
+        false => checked,
+    }
+}
+
+async fn follow(client: &Client, throttle: &Throttle, method: Method, url: &Url) -> Checked {
+    let mut current = url.clone();
+    let mut redirects = Vec::new();
+    loop {
+        let response = {
+            let _permit = throttle.acquire(&current).await;
+            client.request(method.clone(), current.clone()).send().await
+        };
+        let response = match response {
+            Ok(r) => r,
+            Err(e) => return Checked { status: None, redirects, error: Some(e.to_string()) },
+        };
+
+        let next = response
+            .headers()
+            .get(LOCATION)
+            .and_then(|l| l.to_str().ok())
+            .and_then(|l| current.join(l).ok());
+        match next {
+            Some(next) if response.status().is_redirection() => {
+                if redirects.len() == MAX_REDIRECTS {
+                    return Checked { status: Some(response.status().as_u16()), redirects, error: Some(String::from("too many redirects")) };
+                }
+                redirects.push(String::from(next.as_str()));
+                current = next;
+            },
+            _ => return Checked { status: Some(response.status().as_u16()), redirects, error: None },
+        }
+    }
+}
+
+// a csv of broken links for each department with any, one row per page a
+// link is on, so editors can fix them at the source
+pub fn broken_by_department(links: &[Link]) -> BTreeMap<String, String> {
+    let mut reports: BTreeMap<String, String> = BTreeMap::new();
+    for link in links.iter().filter(|l| l.broken()) {
+        for source in link.sources.iter() {
+            let report = reports
+                .entry(source.department.clone())
+                .or_insert_with(|| String::from("page,link,internal,status,error,redirects\n"));
+            *report += &[
+                csv_field(&source.page),
+                csv_field(&link.url),
+                link.internal.to_string(),
+                link.status.map(|s| s.to_string()).unwrap_or_default(),
+                csv_field(link.error.as_deref().unwrap_or_default()),
+                csv_field(&link.redirects.join(" -> ")),
+            ].join(",");
+            *report += "\n";
+        }
+    }
+    reports
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{Read, Write};
+    use std::net::TcpListener;
+
+    fn link(url: &str, status: Option<u16>, error: Option<&str>, pages: &[(&str, &str)]) -> Link {
+        Link {
+            url: String::from(url),
+            internal: true,
+            status,
+            redirects: Vec::new(),
+            error: error.map(String::from),
+            sources: pages
+                .iter()
+                .map(|(department, page)| Source { department: String::from(*department), page: String::from(*page) })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn reports_broken_links_once_per_page_by_department() {
+        let mut moved = link("https://a.edu/old", Some(404), None, &[("dept", "https://a.edu/dept/")]);
+        moved.redirects = vec![String::from("https://a.edu/x"), String::from("https://a.edu/y")];
+        let links = [
+            moved,
+            link("https://a.edu/ok", Some(200), None, &[("dept", "https://a.edu/dept/")]),
+            link("https://b.org/", None, Some("refused, twice"), &[("dept", "https://a.edu/dept/"), ("other", "https://a.edu/other/")]),
+        ];
+
+        let reports = broken_by_department(&links);
+        assert_eq!(reports.keys().collect::<Vec<_>>(), ["dept", "other"]);
+        assert_eq!(reports["dept"], "page,link,internal,status,error,redirects\n\
+            https://a.edu/dept/,https://a.edu/old,true,404,,https://a.edu/x -> https://a.edu/y\n\
+            https://a.edu/dept/,https://b.org/,true,,\"refused, twice\",\n");
+        assert_eq!(reports["other"].lines().count(), 2);
+    }
+
+    // a server that answers every request with a redirect to the next hop
+    fn redirecting_server() -> Url {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        std::thread::spawn(move || {
+            for (hop, stream) in listener.incoming().enumerate() {
+                let mut stream = stream.unwrap();
+                let mut request = [0; 1024];
+                let _ = stream.read(&mut request);
+                let response = format!("HTTP/1.1 302 Found\r\nLocation: /hop{}\r\nContent-Length: 0\r\nConnection: close\r\n\r\n", hop + 1);
+                let _ = stream.write_all(response.as_bytes());
+            }
+        });
+        Url::parse(&format!("http://{address}/hop0")).unwrap()
+    }
+
+    #[tokio::test]
+    async fn gives_up_on_endless_redirects() {
+        let url = redirecting_server();
+        let client = Client::builder().redirect(Policy::none()).build().unwrap();
+        let throttle = Throttle::new(1, 0, None);
+
+        let checked = follow(&client, &throttle, Method::HEAD, &url).await;
+        assert_eq!(checked.status, Some(302));
+        assert_eq!(checked.redirects.len(), MAX_REDIRECTS);
+        assert_eq!(checked.redirects[0], url.join("/hop1").unwrap().as_str());
+        assert_eq!(checked.error.as_deref(), Some("too many redirects"));
+    }
+}
@@ -0,0 +1,164 @@
+use serde::{Deserialize, Serialize};
+
+// one target of a targets.csv or targets.jsonl. `path` is what a line of a
+// plain targets file holds, the department followed by the url extension
+#[derive(Serialize, Deserialize, Default, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct Entry {
+    pub path: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub owner: Option<String>,
+    // higher is pursued first
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub priority: Option<i64>,
+    // overrides of the content region for this page only
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub content_start: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub content_end: Option<String>,
+}
+
+const COLUMNS: [&str; 6] = ["path", "title", "owner", "priority", "content_start", "content_end"];
+
+// the first row names the columns. only `path` is required, in any position
+pub fn read_csv(text: &str, file: &str) -> Vec<Entry> {
+    let mut rows = text.lines().enumerate().filter(|(_, l)| !l.trim().is_empty());
+    let Some((_, header)) = rows.next() else {
+        return Vec::new();
+    };
+    let columns: Vec<String> = split_csv(header).iter().map(|c| c.trim().to_ascii_lowercase()).collect();
+    if !columns.iter().any(|c| c == "path") {
+        println!("{file}: the header has no `path` column");
+        return Vec::new();
+    }
+    for column in columns.iter().filter(|c| !COLUMNS.contains(&c.as_str())) {
+        println!("{file}: unknown column `{column}` is ignored");
+    }
+
+    let mut entries = Vec::new();
+    for (at, row) in rows {
+        let mut entry = Entry::default();
+        for (column, value) in columns.iter().zip(split_csv(row)) {
+            let value = String::from(value.trim());
+            let optional = || (!value.is_empty()).then(|| value.clone());
+            match column.as_str() {
+                "path" => entry.path = value.clone(),
+                "title" => entry.title = optional(),
+                "owner" => entry.owner = optional(),
+                "priority" => entry.priority = match value.parse() {
+                    Ok(p) => Some(p),
+                    Err(_) if value.is_empty() => None,
+                    Err(_) => {
+                        println!("{file}:{}: priority `{value}` is not a number", at + 1);
+                        None
+                    },
+                },
+                "content_start" => entry.content_start = optional(),
+                "content_end" => entry.content_end = optional(),
+                _ => (),
+            }
+        }
+
+        match entry.path.is_empty() {
+            true => println!("{file}:{}: missing path", at + 1),
+            false => entries.push(entry),
+        }
+    }
+    entries
+}
+
+pub fn read_jsonl(text: &str, file: &str) -> Vec<Entry> {
+    text.lines()
+        .enumerate()
+        .filter(|(_, l)| !l.trim().is_empty())
+        .filter_map(|(at, line)| match serde_json::from_str::<Entry>(line) {
+            Ok(entry) if !entry.path.is_empty() => Some(entry),
+            Ok(_) => {
+                println!("{file}:{}: missing path", at + 1);
+                None
+            },
+            Err(e) => {
+                println!("{file}:{}: {e}", at + 1);
+                None
+            },
+        })
+        .collect()
+}
+
+// fields of one csv row, with `"` quoting and `""` escapes
+fn split_csv(row: &str) -> Vec<String> {
+    let mut fields = vec![String::new()];
+    let mut quoted = false;
+    let mut chars = row.chars().peekable();
+    while let Some(c) = chars.next() {
+        let field = fields.last_mut().expect("there is always a field");
+        match (c, quoted) {
+            ('"', true) if chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            },
+            ('"', _) => quoted = !quoted,
+            (',', false) => fields.push(String::new()),
+            (c, _) => field.push(c),
+        }
+    }
+    fields
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn splits_quoted_fields() {
+        assert_eq!(split_csv("a,b,,c"), ["a", "b", "", "c"]);
+        assert_eq!(split_csv(r#""a, b",c"#), ["a, b", "c"]);
+        assert_eq!(split_csv(r#""say ""hi""",x"#), [r#"say "hi""#, "x"]);
+        assert_eq!(split_csv(r#""""#), [""]);
+        assert_eq!(split_csv(""), [""]);
+    }
+
+    #[test]
+    fn reads_csv_columns_in_any_order() {
+        let text = "Priority, path ,title,extra\n\n2,dept/,\"People, staff\",x\n,other/page.html,,\nsoon,md/,,\n";
+        let entries = read_csv(text, "targets.csv");
+        let read: Vec<(&str, Option<&str>, Option<i64>)> = entries
+            .iter()
+            .map(|e| (e.path.as_str(), e.title.as_deref(), e.priority))
+            .collect();
+        assert_eq!(read, [
+            ("dept/", Some("People, staff"), Some(2)),
+            ("other/page.html", None, None),
+            ("md/", None, None),
+        ]);
+    }
+
+    #[test]
+    fn skips_csv_without_a_path() {
+        assert!(read_csv("title,owner\nA,b\n", "targets.csv").is_empty());
+        assert!(read_csv("", "targets.csv").is_empty());
+
+        let entries = read_csv("path,title,content_start,content_end\n,A,,\ndept/,B,\"id=\"\"main\"\"\",\n", "targets.csv");
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].content_start.as_deref(), Some("id=\"main\""));
+        assert_eq!(entries[0].content_end, None);
+    }
+
+    #[test]
+    fn reads_jsonl_and_refuses_unknown_fields() {
+        let text = r#"{"path": "dept/", "owner": "web", "priority": 5}
+
+{"path": "other/", "colour": "blue"}
+{"path": ""}
+not json
+{"path": "md/", "content_end": "div.end"}
+"#;
+        let entries = read_jsonl(text, "targets.jsonl");
+        let paths: Vec<&str> = entries.iter().map(|e| e.path.as_str()).collect();
+        assert_eq!(paths, ["dept/", "md/"]);
+        assert_eq!((entries[0].owner.as_deref(), entries[0].priority), (Some("web"), Some(5)));
+        assert_eq!(entries[1].content_end.as_deref(), Some("div.end"));
+    }
+}
@@ -1,28 +1,186 @@
 use std::env;
 use std::process;
 
-use web_migration::Manager;
+use web_migration::{ErrorKind, Manager, Options, Outcome, Result};
+
+const HELP: &str = "\
+Usage: web_migration <command> <base_path> [arguments] [flags]
+
+Commands:
+  fetch <base_path>                 pursue every target once (the default when no command is given)
+  crawl <base_path>                 like fetch, but also pursue internal pages linked from each target
+  diff <base_path> <from> <to>      compare two captures, each a timestamp or latest/previous
+  report <base_path> <run_id>       write the report of an earlier run again from its journal
+  redirects <base_path> <run_id>    write nginx, apache and csv redirect maps for the pages and files of a run
+  validate-config <base_path>       check the config and print the settings a run would use
+  export <base_path> <run_id>       write the report and redirect maps of a run together
+  convert <base_path>               write config.toml next to config.txt, rules file included
+
+Flags:
+  --config <path>       config file to read instead of <base_path>/config/config.toml or config.txt
+  --base-url <url>      override BaseUrl
+  --targets <path>      override Targets
+  --concurrency <n>     override Concurrency
+  --site <name>         only the named site of a config.toml with several
+  --resume <run_id>     fetch or crawl: skip targets an interrupted run already finished
+  --markdown            also store each page as markdown with front matter
+  --check-links         fetch or crawl: check every link in page content and report broken ones per department
+  --dry-run             fetch or crawl: print the urls that would be pursued and stop
+  --verbose             print every target as it completes
+  -h, --help            print this help
+
+Exit codes:
+  0  every target succeeded
+  1  the run failed, or every target failed
+  2  bad arguments or config
+  3  some targets failed
+";
+
+struct Cli {
+    command: String,
+    arguments: Vec<String>,
+    config: Option<String>,
+    options: Options,
+    dry_run: bool,
+}
 
 fn main() {
     let arguments: Vec<String> = env::args().skip(1).collect();
-    match &arguments[..] {
-        [a] => match Manager::run(a) {
-            Ok(report) => {
-                println!("Application completed successfully");
-                println!("Report Location: {report}");
-            
-                process::exit(0);
-            },
-            Err(e) => {
-                println!("Application error: {e}");
-    
-                process::exit(1);
-            },
+    if arguments.is_empty() || arguments.iter().any(|a| a == "-h" || a == "--help") {
+        print!("{HELP}");
+
+        process::exit(if arguments.is_empty() { 2 } else { 0 });
+    }
+
+    let cli = match parse(arguments) {
+        Ok(cli) => cli,
+        Err(e) => {
+            println!("{e}");
+            println!("Run with --help for usage");
+
+            process::exit(2);
         },
-        _ => {
-            println!("Invalid amount of arguments");
+    };
 
-            process::exit(1);
+    let (c, o) = (cli.config.as_deref(), &cli.options);
+    match (cli.command.as_str(), &cli.arguments[..]) {
+        ("fetch" | "crawl", [a]) if cli.dry_run => finish_lines(Manager::targets(a, c, o)),
+        ("fetch", [a]) => finish_run(Manager::run_with(a, c, o)),
+        ("crawl", [a]) => finish_run(Manager::crawl_with(a, c, o)),
+        ("diff", [a, from, to]) => finish(Manager::diff(a, from, to, c, o), "Report Location"),
+        ("report", [a, run_id]) => finish_run(Manager::report(a, run_id, c, o)),
+        ("redirects", [a, run_id]) => finish(Manager::redirects(a, run_id, c, o), "Redirects Location"),
+        ("validate-config", [a]) => finish_lines(Manager::validate(a, c, o)),
+        ("export", [a, run_id]) => finish_run(Manager::export(a, run_id, c, o)),
+        ("convert", [a]) => finish(Manager::convert(a, c), "Config Location"),
+        (command, _) => {
+            println!("Invalid amount of arguments for {command}");
+            println!("Run with --help for usage");
+
+            process::exit(2);
+        },
+    }
+}
+
+fn parse(arguments: Vec<String>) -> std::result::Result<Cli, String> {
+    let mut cli = Cli {
+        command: String::new(),
+        arguments: Vec::new(),
+        config: None,
+        options: Options::default(),
+        dry_run: false,
+    };
+
+    let mut arguments = arguments.into_iter();
+    while let Some(argument) = arguments.next() {
+        let mut value = || arguments.next().ok_or(format!("{argument} needs a value"));
+        match argument.as_str() {
+            "--config" => cli.config = Some(value()?),
+            "--base-url" => cli.options.base_url = Some(value()?),
+            "--targets" => cli.options.targets = Some(value()?),
+            "--concurrency" => cli.options.concurrency = Some(
+                value()?.parse().map_err(|_| String::from("--concurrency needs a number"))?
+            ),
+            "--site" => cli.options.site = Some(value()?),
+            "--resume" => cli.options.resume = Some(value()?),
+            "--markdown" => cli.options.markdown = true,
+            "--check-links" => cli.options.check_links = true,
+            "--dry-run" => cli.dry_run = true,
+            "--verbose" => cli.options.verbose = true,
+            flag if flag.starts_with("--") => return Err(format!("Unknown flag {flag}")),
+            _ => cli.arguments.push(argument),
         }
     }
+
+    // a bare base path fetches, as it always has
+    cli.command = match cli.arguments.first().map(String::as_str) {
+        Some("fetch" | "crawl" | "diff" | "report" | "redirects" | "validate-config" | "export" | "convert") => cli.arguments.remove(0),
+        _ => String::from("fetch"),
+    };
+
+    if cli.dry_run && cli.command != "fetch" && cli.command != "crawl" {
+        return Err(format!("--dry-run does not apply to {}", cli.command));
+    }
+    if cli.options.resume.is_some() && cli.command != "fetch" && cli.command != "crawl" {
+        return Err(format!("--resume does not apply to {}", cli.command));
+    }
+    if cli.options.check_links && cli.command != "fetch" && cli.command != "crawl" {
+        return Err(format!("--check-links does not apply to {}", cli.command));
+    }
+
+    Ok(cli)
+}
+
+fn finish(result: Result<String>, label: &str) {
+    match result {
+        Ok(location) => {
+            println!("Application completed successfully");
+            println!("{label}: {location}");
+
+            process::exit(0);
+        },
+        Err(e) => fail(e),
+    }
+}
+
+fn finish_run(result: Result<Outcome>) {
+    match result {
+        Ok(outcome) => {
+            if outcome.failed == 0 {
+                println!("Application completed successfully");
+            } else {
+                println!("Application completed with {} of {} targets failed", outcome.failed, outcome.pursued);
+            }
+            println!("Report Location: {}", outcome.report);
+
+            process::exit(match outcome.failed {
+                0 => 0,
+                f if f == outcome.pursued => 1,
+                _ => 3,
+            });
+        },
+        Err(e) => fail(e),
+    }
+}
+
+fn finish_lines(result: Result<Vec<String>>) {
+    match result {
+        Ok(lines) => {
+            for line in lines {
+                println!("{line}");
+            }
+
+            process::exit(0);
+        },
+        Err(e) => fail(e),
+    }
+}
+
+fn fail(e: web_migration::Error) {
+    println!("Application error: {e}");
+
+    process::exit(match e.kind() {
+        ErrorKind::Config(_) => 2,
+        _ => 1,
+    });
 }
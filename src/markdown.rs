@@ -0,0 +1,286 @@
+use crate::html::{decode_entities, Token, Tokenizer};
+
+// a list being written, and how many items it has had
+struct List {
+    ordered: bool,
+    items: usize,
+}
+
+// markdown for an html fragment, best read from html::clean_fragment.
+// markup with no markdown form is dropped and its text kept
+pub fn from_html(fragment: &str) -> String {
+    let mut out = Writer::default();
+
+    for (_, token) in Tokenizer::new(fragment.as_bytes()) {
+        match token {
+            Token::Start(tag) => {
+                let attribute = |name: &str| String::from(tag.get(name).unwrap_or_default());
+                match tag.name.as_str() {
+                    h if heading_level(h).is_some() => {
+                        out.block();
+                        out.push(&("#".repeat(heading_level(h).unwrap_or(1)) + " "));
+                    },
+                    "p" | "section" | "article" | "figure" => out.block(),
+                    "br" => out.push("  \n"),
+                    "hr" => {
+                        out.block();
+                        out.push("---");
+                        out.block();
+                    },
+                    "ul" | "ol" => {
+                        if out.lists.is_empty() {
+                            out.block();
+                        }
+                        out.lists.push(List { ordered: tag.name == "ol", items: 0 });
+                    },
+                    "li" => out.item(),
+                    "strong" | "b" => out.push("**"),
+                    "em" | "i" => out.push("_"),
+                    "code" if out.pre == 0 => {
+                        out.push("`");
+                        out.code += 1;
+                    },
+                    "pre" => {
+                        out.block();
+                        out.push("```\n");
+                        out.pre += 1;
+                    },
+                    "blockquote" => {
+                        out.block();
+                        out.buffers.push(String::new());
+                    },
+                    "a" => {
+                        out.links.push(attribute("href"));
+                        out.push("[");
+                    },
+                    "img" => out.push(&format!("![{}]({})", escape(&attribute("alt")), attribute("src"))),
+                    "table" => {
+                        out.block();
+                        out.tables.push(Vec::new());
+                    },
+                    "tr" => if let Some(table) = out.tables.last_mut() {
+                        table.push(Vec::new());
+                    },
+                    "td" | "th" => out.buffers.push(String::new()),
+                    _ => (),
+                }
+            },
+            Token::End(name) => match name.as_str() {
+                h if heading_level(h).is_some() => out.block(),
+                "p" | "section" | "article" | "figure" => out.block(),
+                "ul" | "ol" => {
+                    out.lists.pop();
+                    if out.lists.is_empty() {
+                        out.block();
+                    }
+                },
+                "strong" | "b" => out.push("**"),
+                "em" | "i" => out.push("_"),
+                "code" if out.pre == 0 && out.code > 0 => {
+                    out.code -= 1;
+                    out.push("`");
+                },
+                "pre" if out.pre > 0 => {
+                    out.pre -= 1;
+                    out.trim_end();
+                    out.push("\n```");
+                    out.block();
+                },
+                "blockquote" => if let Some(quote) = out.buffers.pop() {
+                    let quoted: Vec<String> = quote
+                        .trim()
+                        .lines()
+                        .map(|l| match l.trim_end() {
+                            "" => String::from(">"),
+                            l => String::from("> ") + l,
+                        })
+                        .collect();
+                    out.push(&quoted.join("\n"));
+                    out.block();
+                },
+                "a" => if let Some(href) = out.links.pop() {
+                    out.push(&format!("]({href})"));
+                },
+                "td" | "th" => if let Some(cell) = out.buffers.pop() {
+                    let cell = cell.split_whitespace().collect::<Vec<_>>().join(" ").replace('|', "\\|");
+                    if let Some(row) = out.tables.last_mut().and_then(|t| t.last_mut()) {
+                        row.push(cell);
+                    }
+                },
+                "table" => if let Some(table) = out.tables.pop() {
+                    out.push(&render_table(&table));
+                    out.block();
+                },
+                _ => (),
+            },
+            Token::Text(text) => {
+                let text = decode_entities(&text);
+                if out.pre > 0 {
+                    out.push(&text);
+                } else if out.code > 0 {
+                    out.text(&text);
+                } else {
+                    out.text(&escape(&text));
+                }
+            },
+            Token::Comment(_) | Token::Declaration(_) => (),
+        }
+    }
+
+    out.finish()
+}
+
+#[derive(Default)]
+struct Writer {
+    out: String,
+    // blockquotes and table cells are written apart, then folded in
+    buffers: Vec<String>,
+    lists: Vec<List>,
+    links: Vec<String>,
+    tables: Vec<Vec<Vec<String>>>,
+    pre: usize,
+    code: usize,
+}
+
+impl Writer {
+    fn current(&mut self) -> &mut String {
+        self.buffers.last_mut().unwrap_or(&mut self.out)
+    }
+
+    fn push(&mut self, s: &str) {
+        self.current().push_str(s);
+    }
+
+    // whitespace collapses to one space, and none at the start of a line
+    fn text(&mut self, text: &str) {
+        let current = self.current();
+        let mut last_space = current.is_empty() || current.ends_with([' ', '\n']);
+        for c in text.chars() {
+            if c.is_whitespace() {
+                if !last_space {
+                    current.push(' ');
+                }
+                last_space = true;
+            } else {
+                current.push(c);
+                last_space = false;
+            }
+        }
+    }
+
+    fn trim_end(&mut self) {
+        let current = self.current();
+        current.truncate(current.trim_end().len());
+    }
+
+    // ends the paragraph being written, unless inside a list item
+    fn block(&mut self) {
+        if !self.lists.is_empty() {
+            return;
+        }
+        self.trim_end();
+        if !self.current().is_empty() {
+            self.push("\n\n");
+        }
+    }
+
+    fn item(&mut self) {
+        let depth = self.lists.len();
+        let Some(list) = self.lists.last_mut() else {
+            return;
+        };
+        list.items += 1;
+        let marker = match list.ordered {
+            true => format!("{}. ", list.items),
+            false => String::from("- "),
+        };
+
+        if !self.current().is_empty() && !self.current().ends_with('\n') {
+            self.trim_end();
+            self.push("\n");
+        }
+        self.push(&("  ".repeat(depth - 1) + &marker));
+    }
+
+    fn finish(mut self) -> String {
+        while let Some(buffer) = self.buffers.pop() {
+            self.push(&buffer);
+        }
+        String::from(self.out.trim()) + "\n"
+    }
+}
+
+fn heading_level(name: &str) -> Option<usize> {
+    match name.as_bytes() {
+        [b'h', n @ b'1'..=b'6'] => Some((n - b'0') as usize),
+        _ => None,
+    }
+}
+
+// the first row is the header, as markdown has no table without one
+fn render_table(rows: &[Vec<String>]) -> String {
+    let width = rows.iter().map(Vec::len).max().unwrap_or(0);
+    if width == 0 {
+        return String::new();
+    }
+
+    let line = |row: &[String]| {
+        let cells: Vec<&str> = (0..width).map(|i| row.get(i).map(String::as_str).unwrap_or_default()).collect();
+        String::from("| ") + &cells.join(" | ") + " |"
+    };
+    let mut lines = vec![line(&rows[0]), String::from("|") + &" --- |".repeat(width)];
+    lines.extend(rows[1..].iter().map(|r| line(r)));
+    lines.join("\n")
+}
+
+fn escape(text: &str) -> String {
+    text.chars().fold(String::new(), |mut acc, c| {
+        if matches!(c, '\\' | '*' | '_' | '`' | '[' | ']') {
+            acc.push('\\');
+        }
+        acc.push(c);
+        acc
+    })
+}
+
+// yaml front matter. json strings are valid yaml scalars
+pub fn front_matter(fields: &[(&str, &str)]) -> String {
+    let mut out = String::from("---\n");
+    for (key, value) in fields {
+        out = out + key + ": " + &serde_json::to_string(value).unwrap_or_default() + "\n";
+    }
+    out + "---\n\n"
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn converts_markup_with_a_markdown_form() {
+        let cases = [
+            ("<h1>Title</h1><h3>Sub</h3><p>Body</p>", "# Title\n\n### Sub\n\nBody\n"),
+            ("<p>one</p>\n\n  <p>two\n  lines</p>", "one\n\ntwo lines\n"),
+            ("<ul><li>a</li><li>b</li></ul>", "- a\n- b\n"),
+            ("<ol><li>a</li><li>b</li></ol>", "1. a\n2. b\n"),
+            ("<ul><li>a<ol><li>x</li><li>y</li></ol></li><li>b</li></ul>", "- a\n  1. x\n  2. y\n- b\n"),
+            ("<table><tr><th>Name</th><th>Room</th></tr><tr><td>Ada</td><td>1|2</td></tr></table>", "| Name | Room |\n| --- | --- |\n| Ada | 1\\|2 |\n"),
+            ("<p>See <a href=\"/x\">the page</a>.</p>", "See [the page](/x).\n"),
+            ("<img src=\"/p.png\" alt=\"a [map]\">", "![a \\[map\\]](/p.png)\n"),
+            ("<p><strong>bold</strong>, <em>soft</em> and <b>b</b><i>i</i></p>", "**bold**, _soft_ and **b**_i_\n"),
+            ("<p>Run <code>a_b *c*</code> &amp; 2*3</p>", "Run `a_b *c*` & 2\\*3\n"),
+            ("<pre><code>fn main() {\n    x &lt; 1;\n}\n</code></pre>", "```\nfn main() {\n    x < 1;\n}\n```\n"),
+            ("<blockquote><p>one</p><p>two</p></blockquote>", "> one\n>\n> two\n"),
+            ("<p>a<br>b</p><hr><p>c</p>", "a  \nb\n\n---\n\nc\n"),
+            ("<p>kept <span class=\"x\">text</span></p>", "kept text\n"),
+        ];
+        for (html, markdown) in cases {
+            assert_eq!(from_html(html), markdown, "for {html}");
+        }
+    }
+
+    #[test]
+    fn front_matter_quotes_values() {
+        assert_eq!(front_matter(&[("title", "A \"b\": c")]), "---\ntitle: \"A \\\"b\\\": c\"\n---\n\n");
+    }
+}
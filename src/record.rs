@@ -0,0 +1,153 @@
+use serde::{Deserialize, Serialize};
+
+use crate::listing::Entry;
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Failure {
+    Disallowed,
+    Network,
+    Status,
+    Storage,
+    Asset,
+}
+
+impl Failure {
+    fn name(&self) -> &'static str {
+        match self {
+            Failure::Disallowed => "disallowed",
+            Failure::Network => "network",
+            Failure::Status => "status",
+            Failure::Storage => "storage",
+            Failure::Asset => "asset",
+        }
+    }
+}
+
+// what a rewritten link pointed at
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum LinkKind {
+    Asset,
+    Page,
+}
+
+impl LinkKind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            LinkKind::Asset => "asset",
+            LinkKind::Page => "page",
+        }
+    }
+}
+
+// a link in a page's content moved to where its target now lives
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Rewrite {
+    pub kind: LinkKind,
+    pub from: String,
+    pub to: String,
+}
+
+// a file downloaded for a page, and the url it came from
+#[derive(Serialize, Deserialize, Clone)]
+pub struct SavedFile {
+    pub url: String,
+    pub file: String,
+}
+
+// what happened to one target during a run
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Record {
+    // the site and target this record is for, so a resumed run can find it again
+    #[serde(default)]
+    pub site: String,
+    pub target: String,
+    pub url: String,
+    pub final_url: Option<String>,
+    pub status: Option<u16>,
+    pub bytes: usize,
+    pub content_type: Option<String>,
+    pub stored: Option<String>,
+    pub assets: usize,
+    pub retries: usize,
+    pub unchanged: bool,
+    pub error_kind: Option<Failure>,
+    pub error: Option<String>,
+    pub duration_ms: u128,
+    // from an extended targets file
+    #[serde(default)]
+    pub title: Option<String>,
+    #[serde(default)]
+    pub owner: Option<String>,
+    #[serde(default)]
+    pub rewrites: Vec<Rewrite>,
+    #[serde(default)]
+    pub files: Vec<SavedFile>,
+    // the target as it was listed, so a resumed run retries it with its labels
+    #[serde(default)]
+    pub entry: Option<Entry>,
+}
+
+impl Record {
+    pub fn new(site: &str, target: &str, url: &str) -> Self {
+        Self {
+            site: String::from(site),
+            target: String::from(target),
+            url: String::from(url),
+            final_url: None,
+            status: None,
+            bytes: 0,
+            content_type: None,
+            stored: None,
+            assets: 0,
+            retries: 0,
+            unchanged: false,
+            error_kind: None,
+            error: None,
+            duration_ms: 0,
+            title: None,
+            owner: None,
+            rewrites: Vec::new(),
+            files: Vec::new(),
+            entry: None,
+        }
+    }
+
+    pub fn fail(&mut self, kind: Failure, message: String) {
+        println!("{}: {message}", self.url);
+        self.error_kind = Some(kind);
+        self.error = Some(message);
+    }
+
+    pub const CSV_HEADER: &'static str = "url,final_url,status,bytes,content_type,stored,assets,retries,unchanged,error_kind,error,duration_ms,title,owner,rewrites";
+
+    pub fn to_csv(&self) -> String {
+        let optional = |v: &Option<String>| csv_field(v.as_deref().unwrap_or_default());
+        [
+            csv_field(&self.url),
+            optional(&self.final_url),
+            self.status.map(|s| s.to_string()).unwrap_or_default(),
+            self.bytes.to_string(),
+            optional(&self.content_type),
+            optional(&self.stored),
+            self.assets.to_string(),
+            self.retries.to_string(),
+            self.unchanged.to_string(),
+            self.error_kind.map(|k| String::from(k.name())).unwrap_or_default(),
+            optional(&self.error),
+            self.duration_ms.to_string(),
+            optional(&self.title),
+            optional(&self.owner),
+            self.rewrites.len().to_string(),
+        ].join(",")
+    }
+}
+
+pub fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        String::from("\"") + &value.replace('"', "\"\"") + "\""
+    } else {
+        String::from(value)
+    }
+}
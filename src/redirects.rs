@@ -0,0 +1,155 @@
+use std::collections::BTreeMap;
+
+use percent_encoding::percent_decode_str;
+use url::Url;
+
+use crate::record::{csv_field, LinkKind};
+
+// a legacy url and where what it served lives after the migration
+pub struct Redirect {
+    pub from: Url,
+    pub to: String,
+    pub kind: LinkKind,
+}
+
+impl Redirect {
+    // what the old server is asked for, as it arrives in the request line
+    fn request(&self) -> String {
+        match self.from.query() {
+            Some(q) => String::from(self.from.path()) + "?" + q,
+            None => String::from(self.from.path()),
+        }
+    }
+}
+
+// new paths that more than one old url ends up at. for pages it means two
+// targets share a slug, for files that one download overwrote another
+pub fn collisions(redirects: &[Redirect]) -> Vec<String> {
+    let mut by_path: BTreeMap<&str, Vec<&str>> = BTreeMap::new();
+    for r in redirects {
+        by_path.entry(&r.to).or_default().push(r.from.as_str());
+    }
+
+    by_path
+        .into_iter()
+        .filter(|(_, from)| from.len() > 1)
+        .map(|(to, from)| format!("{to} is the new path of {}", from.join(", ")))
+        .collect()
+}
+
+pub fn nginx(redirects: &[Redirect]) -> String {
+    let mut out = String::from("# include in the http block, then in the server block of the old site:\n");
+    out += "#     if ($redirect_to) { return 301 $redirect_to; }\n";
+    out += "map $request_uri $redirect_to {\n";
+    for r in redirects {
+        let key = r.request().replace('\\', "\\\\").replace('"', "\\\"");
+        // values are expanded like any nginx string, and `$` has no escape
+        let value = r.to.replace('\\', "%5C").replace('"', "%22").replace('$', "%24");
+        out = out + "    \"" + &key + "\" \"" + &value + "\";\n";
+    }
+    out + "}\n"
+}
+
+// rules for the old site's config or .htaccess, after `RewriteEngine On`.
+// RewriteRule matches the decoded path without its query, so a query is
+// matched by a condition of its own
+pub fn apache(redirects: &[Redirect]) -> String {
+    let mut out = String::new();
+    for r in redirects {
+        let path = percent_decode_str(r.from.path().trim_start_matches('/')).decode_utf8_lossy();
+        let target = r.to.replace('\\', "\\\\").replace('%', "\\%").replace('$', "\\$");
+        match r.from.query() {
+            Some(q) => {
+                out = out + "RewriteCond %{QUERY_STRING} \"^" + &escape(q) + "$\"\n";
+                out = out + "RewriteRule \"^/?" + &escape(&path) + "$\" \"" + &target + "\" [R=301,L,NE,QSD]\n";
+            },
+            None => out = out + "RewriteRule \"^/?" + &escape(&path) + "$\" \"" + &target + "\" [R=301,L,NE]\n",
+        }
+    }
+    out
+}
+
+pub fn csv(redirects: &[Redirect]) -> String {
+    redirects.iter().fold(String::from("old_url,new_path,kind\n"), |acc, r| {
+        acc + &csv_field(r.from.as_str()) + "," + &csv_field(&r.to) + "," + r.kind.name() + "\n"
+    })
+}
+
+// a regex matching `text` and nothing else
+fn escape(text: &str) -> String {
+    text.chars().fold(String::new(), |mut acc, c| {
+        if "\\.^$|?*+()[]{}\"".contains(c) {
+            acc.push('\\');
+        }
+        acc.push(c);
+        acc
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn redirect(from: &str, to: &str) -> Redirect {
+        Redirect { from: Url::parse(from).unwrap(), to: String::from(to), kind: LinkKind::Page }
+    }
+
+    #[test]
+    fn nginx_keys_are_request_uris_and_values_never_expand() {
+        let map = nginx(&[
+            redirect("https://a.edu/as/dept/", "/dept"),
+            redirect("https://a.edu/as/list.php?page=2&sort=a", "/list"),
+            redirect("https://a.edu/as/my file.pdf", "/docs/my%20file.pdf"),
+            redirect("https://a.edu/as/odd", "/price$5/\"quoted\""),
+        ]);
+        let entries: Vec<&str> = map.lines().filter(|l| l.starts_with("    ")).collect();
+        assert_eq!(entries, [
+            r#"    "/as/dept/" "/dept";"#,
+            r#"    "/as/list.php?page=2&sort=a" "/list";"#,
+            r#"    "/as/my%20file.pdf" "/docs/my%20file.pdf";"#,
+            r#"    "/as/odd" "/price%245/%22quoted%22";"#,
+        ]);
+        assert!(map.contains("map $request_uri $redirect_to {\n"));
+        assert!(map.ends_with("}\n"));
+    }
+
+    #[test]
+    fn apache_matches_decoded_paths_and_queries_apart() {
+        let rules = apache(&[
+            redirect("https://a.edu/as/page.html", "/page"),
+            redirect("https://a.edu/as/list.php?page=2", "/list"),
+            redirect("https://a.edu/as/my%20file (1).pdf", "/docs/my%20file$1.pdf"),
+        ]);
+        assert_eq!(rules, concat!(
+            "RewriteRule \"^/?as/page\\.html$\" \"/page\" [R=301,L,NE]\n",
+            "RewriteCond %{QUERY_STRING} \"^page=2$\"\n",
+            "RewriteRule \"^/?as/list\\.php$\" \"/list\" [R=301,L,NE,QSD]\n",
+            "RewriteRule \"^/?as/my file \\(1\\)\\.pdf$\" \"/docs/my\\%20file\\$1.pdf\" [R=301,L,NE]\n",
+        ));
+    }
+
+    #[test]
+    fn escapes_every_regex_metacharacter() {
+        assert_eq!(escape("a.b*c+d?e(f)[g]{h}|i^j$k\\l\"m"), "a\\.b\\*c\\+d\\?e\\(f\\)\\[g\\]\\{h\\}\\|i\\^j\\$k\\\\l\\\"m");
+        assert_eq!(escape("plain/path-1_2"), "plain/path-1_2");
+    }
+
+    #[test]
+    fn finds_old_urls_sharing_a_new_path() {
+        let found = collisions(&[
+            redirect("https://a.edu/as/dept/", "/dept"),
+            redirect("https://a.edu/as/dept/index.html", "/dept"),
+            redirect("https://a.edu/as/other/", "/other"),
+        ]);
+        assert_eq!(found, ["/dept is the new path of https://a.edu/as/dept/, https://a.edu/as/dept/index.html"]);
+        assert!(collisions(&[redirect("https://a.edu/as/other/", "/other")]).is_empty());
+    }
+
+    #[test]
+    fn csv_lists_every_redirect() {
+        let mut file = redirect("https://a.edu/sites/default/files/a,b.pdf", "/documents/dept/a,b.pdf");
+        file.kind = LinkKind::Asset;
+        assert_eq!(csv(&[file]), "old_url,new_path,kind\n\
+            \"https://a.edu/sites/default/files/a,b.pdf\",\"/documents/dept/a,b.pdf\",asset\n");
+    }
+}
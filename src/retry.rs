@@ -0,0 +1,152 @@
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::time::Duration;
+use chrono::{DateTime, Utc};
+use reqwest::header::{HeaderMap, RETRY_AFTER};
+use reqwest::StatusCode;
+
+#[derive(Clone)]
+pub struct RetryPolicy {
+    pub attempts: usize,
+    pub delay: Duration,
+    pub jitter: Duration,
+    pub max_delay: Duration,
+    pub statuses: Vec<u16>,
+}
+
+impl RetryPolicy {
+    pub fn build(attempts: usize, delay: u64, jitter: u64, max_delay: u64, statuses: Vec<u16>) -> Self {
+        Self {
+            attempts: attempts.max(1),
+            delay: Duration::from_millis(delay),
+            jitter: Duration::from_millis(jitter),
+            max_delay: Duration::from_millis(max_delay),
+            statuses: if statuses.is_empty() { vec![429, 502, 503, 504] } else { statuses },
+        }
+    }
+
+    pub fn retryable(&self, status: StatusCode) -> bool {
+        self.statuses.contains(&status.as_u16())
+    }
+
+    // doubles the base delay for every attempt already made, plus up to
+    // `jitter`, never past `max_delay`
+    pub fn backoff(&self, attempt: usize) -> Duration {
+        let factor = 2u32.saturating_pow(attempt.saturating_sub(1) as u32);
+        (self.delay.saturating_mul(factor) + random_fraction(self.jitter)).min(self.max_delay)
+    }
+
+    // the server's Retry-After wins when it asks for a longer wait than the
+    // backoff. one longer than `max_delay` is returned as the error
+    pub fn wait(&self, attempt: usize, headers: Option<&HeaderMap>) -> Result<Duration, Duration> {
+        let backoff = self.backoff(attempt);
+        match headers.and_then(retry_after) {
+            Some(after) if after > self.max_delay => Err(after),
+            Some(after) => Ok(after.max(backoff)),
+            None => Ok(backoff),
+        }
+    }
+}
+
+// one request made for a page, and how long we waited before the next one
+pub struct Attempt {
+    pub status: Option<u16>,
+    pub error: Option<String>,
+    pub waited: Duration,
+}
+
+impl Attempt {
+    pub fn describe(&self) -> String {
+        let outcome = match (&self.status, &self.error) {
+            (_, Some(e)) => String::from(e),
+            (Some(s), None) => s.to_string(),
+            (None, None) => String::from("no response"),
+        };
+
+        if self.waited.is_zero() {
+            outcome
+        } else {
+            format!("{outcome} (waited {}ms)", self.waited.as_millis())
+        }
+    }
+}
+
+// a request that could not be built, or that redirects in circles, fails
+// the same way every time
+pub fn can_succeed(error: &reqwest::Error) -> bool {
+    !error.is_builder() && !error.is_redirect()
+}
+
+fn retry_after(headers: &HeaderMap) -> Option<Duration> {
+    let value = headers.get(RETRY_AFTER)?.to_str().ok()?.trim();
+    match value.parse::<u64>() {
+        Ok(seconds) => Some(Duration::from_secs(seconds)),
+        Err(_) => DateTime::parse_from_rfc2822(value)
+            .ok()
+            .and_then(|date| (date.with_timezone(&Utc) - Utc::now()).to_std().ok()),
+    }
+}
+
+fn random_fraction(max: Duration) -> Duration {
+    let random = RandomState::new().build_hasher().finish();
+    max.mul_f64((random % 1000) as f64 / 1000.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reqwest::header::HeaderValue;
+
+    fn policy() -> RetryPolicy {
+        RetryPolicy::build(5, 100, 0, 1000, Vec::new())
+    }
+
+    fn asking(value: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(RETRY_AFTER, HeaderValue::from_str(value).unwrap());
+        headers
+    }
+
+    #[test]
+    fn backoff_doubles_up_to_the_maximum() {
+        let waits: Vec<u128> = (1..=6).map(|a| policy().backoff(a).as_millis()).collect();
+        assert_eq!(waits, [100, 200, 400, 800, 1000, 1000]);
+    }
+
+    #[test]
+    fn jitter_stays_within_its_bound() {
+        let policy = RetryPolicy::build(3, 100, 50, 1000, Vec::new());
+        for _ in 0..20 {
+            let wait = policy.backoff(1);
+            assert!(wait >= Duration::from_millis(100) && wait <= Duration::from_millis(150));
+        }
+    }
+
+    #[test]
+    fn reads_retry_after_seconds_and_dates() {
+        assert_eq!(retry_after(&asking("7")), Some(Duration::from_secs(7)));
+        assert_eq!(retry_after(&asking(" 0 ")), Some(Duration::ZERO));
+
+        let later = (Utc::now() + chrono::Duration::seconds(120)).to_rfc2822();
+        let wait = retry_after(&asking(&later)).unwrap();
+        assert!(wait > Duration::from_secs(110) && wait <= Duration::from_secs(120));
+
+        // a date already past asks for no wait at all
+        assert_eq!(retry_after(&asking("Wed, 21 Oct 2015 07:28:00 GMT")), None);
+        assert_eq!(retry_after(&asking("soon")), None);
+    }
+
+    #[test]
+    fn retry_after_wins_until_it_passes_the_maximum() {
+        assert_eq!(policy().wait(1, None), Ok(Duration::from_millis(100)));
+        assert_eq!(policy().wait(1, Some(&asking("0"))), Ok(Duration::from_millis(100)));
+        assert_eq!(policy().wait(1, Some(&asking("1"))), Ok(Duration::from_secs(1)));
+        assert_eq!(policy().wait(1, Some(&asking("86400"))), Err(Duration::from_secs(86400)));
+    }
+
+    #[test]
+    fn builder_errors_are_not_retried() {
+        let error = reqwest::Client::new().get("not a url").build().unwrap_err();
+        assert!(!can_succeed(&error));
+    }
+}
@@ -0,0 +1,207 @@
+use std::time::Duration;
+use reqwest::{Client, Url};
+
+use crate::Result;
+
+struct Group {
+    agents: Vec<String>,
+    rules: Vec<(bool, String)>,
+    crawl_delay: Option<Duration>,
+}
+
+impl Group {
+    fn new() -> Self {
+        Self {
+            agents: Vec::new(),
+            rules: Vec::new(),
+            crawl_delay: None,
+        }
+    }
+}
+
+// the part of a robots.txt that applies to one user agent
+pub struct Robots {
+    rules: Vec<(bool, String)>,
+    pub crawl_delay: Option<Duration>,
+}
+
+impl Robots {
+    pub fn allow_all() -> Self {
+        Self {
+            rules: Vec::new(),
+            crawl_delay: None,
+        }
+    }
+
+    pub fn parse(text: &str, user_agent: &str) -> Self {
+        let mut groups: Vec<Group> = Vec::new();
+        let mut current = Group::new();
+        let mut in_rules = false;
+
+        for line in text.lines() {
+            let line = line.split('#').next().unwrap_or_default().trim();
+            let (key, value) = match line.split_once(':') {
+                Some((k, v)) => (k.trim().to_ascii_lowercase(), v.trim()),
+                None => continue,
+            };
+
+            match key.as_str() {
+                "user-agent" => {
+                    // a user-agent after rules starts a new group
+                    if in_rules {
+                        groups.push(current);
+                        current = Group::new();
+                        in_rules = false;
+                    }
+                    current.agents.push(value.to_ascii_lowercase());
+                },
+                "allow" | "disallow" => {
+                    in_rules = true;
+                    if !value.is_empty() {
+                        current.rules.push((key == "allow", String::from(value)));
+                    }
+                },
+                "crawl-delay" => {
+                    in_rules = true;
+                    current.crawl_delay = value.parse::<f64>().ok().map(Duration::from_secs_f64);
+                },
+                _ => (),
+            }
+        }
+        groups.push(current);
+
+        // the most specific matching agent wins, then `*`
+        let product = user_agent
+            .split('/')
+            .next()
+            .unwrap_or_default()
+            .to_ascii_lowercase();
+        let best = groups
+            .into_iter()
+            .filter_map(|g| {
+                let score = g.agents
+                    .iter()
+                    .filter_map(|a| match a.as_str() {
+                        "*" => Some(0),
+                        a if !a.is_empty() && product.contains(a) => Some(a.len()),
+                        _ => None,
+                    })
+                    .max()?;
+                Some((score, g))
+            })
+            .max_by_key(|(score, _)| *score);
+
+        match best {
+            Some((_, g)) => Self {
+                rules: g.rules,
+                crawl_delay: g.crawl_delay,
+            },
+            None => Robots::allow_all(),
+        }
+    }
+
+    // the longest matching rule decides, allow wins ties
+    pub fn allowed(&self, path: &str) -> bool {
+        self.rules
+            .iter()
+            .filter(|(_, pattern)| matches(pattern, path))
+            .max_by_key(|(allow, pattern)| (pattern.len(), *allow))
+            .map(|(allow, _)| *allow)
+            .unwrap_or(true)
+    }
+}
+
+// robots.txt patterns are prefixes with `*` wildcards and an optional `$` anchor
+fn matches(pattern: &str, path: &str) -> bool {
+    match pattern.split_once('*') {
+        Some((head, tail)) => path.starts_with(head) && (head.len()..=path.len())
+            .filter(|&i| path.is_char_boundary(i))
+            .any(|i| matches(tail, &path[i..])),
+        None => match pattern.strip_suffix('$') {
+            Some(p) => path == p,
+            None => path.starts_with(pattern),
+        },
+    }
+}
+
+pub async fn fetch(client: &Client, base_url: &str, user_agent: &str) -> Result<Robots> {
+    let url = Url::parse(base_url)?.join("/robots.txt")?;
+    let response = client.get(url).send().await?;
+
+    if response.status().is_success() {
+        Ok(Robots::parse(&response.text().await?, user_agent))
+    } else {
+        Ok(Robots::allow_all())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ROBOTS: &str = "\
+User-agent: *
+Disallow: /private/
+Crawl-delay: 2
+
+# a group naming two agents
+User-agent: web_migration
+User-agent: other
+Disallow: /
+Allow: /as/
+Disallow: /as/drafts/
+Allow: /as/drafts/public.html$
+Crawl-delay: 0.5
+
+User-agent: web
+Disallow: /as/
+";
+
+    #[test]
+    fn picks_the_most_specific_matching_group() {
+        let robots = Robots::parse(ROBOTS, "web_migration/0.1.0");
+        assert_eq!(robots.crawl_delay, Some(Duration::from_millis(500)));
+        assert!(robots.allowed("/as/dept/"));
+        assert!(!robots.allowed("/private/"));
+
+        let robots = Robots::parse(ROBOTS, "Other/2.0");
+        assert!(robots.allowed("/as/dept/"));
+
+        let robots = Robots::parse(ROBOTS, "curl/8.0");
+        assert_eq!(robots.crawl_delay, Some(Duration::from_secs(2)));
+        assert!(robots.allowed("/as/dept/"));
+        assert!(!robots.allowed("/private/x"));
+    }
+
+    #[test]
+    fn the_longest_rule_decides_and_allow_wins_ties() {
+        let robots = Robots::parse(ROBOTS, "web_migration");
+        assert!(!robots.allowed("/other/"));
+        assert!(robots.allowed("/as/"));
+        assert!(!robots.allowed("/as/drafts/x.html"));
+        assert!(robots.allowed("/as/drafts/public.html"));
+        assert!(!robots.allowed("/as/drafts/public.html?v=2"));
+
+        let robots = Robots::parse("User-agent: *\nDisallow: /a\nAllow: /a\n", "any");
+        assert!(robots.allowed("/a"));
+    }
+
+    #[test]
+    fn matches_wildcards_and_anchors() {
+        assert!(matches("/*.pdf$", "/as/files/a.pdf"));
+        assert!(!matches("/*.pdf$", "/as/files/a.pdf?x"));
+        assert!(matches("/as/*/edit", "/as/dept/edit/1"));
+        assert!(!matches("/as/*/edit", "/as/dept/view"));
+        assert!(matches("/", "/anything"));
+    }
+
+    #[test]
+    fn no_matching_group_or_an_empty_disallow_allows_everything() {
+        let robots = Robots::parse("User-agent: googlebot\nDisallow: /\n", "web_migration");
+        assert!(robots.allowed("/as/"));
+        assert_eq!(robots.crawl_delay, None);
+
+        let robots = Robots::parse("User-agent: *\nDisallow:\n", "web_migration");
+        assert!(robots.allowed("/as/"));
+    }
+}
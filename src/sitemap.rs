@@ -0,0 +1,154 @@
+use std::collections::HashSet;
+use std::fs;
+use std::io::Read;
+use bytes::Bytes;
+use flate2::read::GzDecoder;
+use reqwest::Client;
+
+use crate::html::{decode_entities, Token, Tokenizer};
+use crate::Result;
+
+// how many sitemap files an index may lead us through before giving up
+const MAX_SITEMAPS: usize = 1000;
+
+pub struct Sitemap {
+    pub index: bool,
+    pub locations: Vec<String>,
+}
+
+impl Sitemap {
+    pub fn parse(data: &[u8]) -> Self {
+        let mut index = false;
+        let mut locations = Vec::new();
+        let mut loc: Option<String> = None;
+
+        for (_, token) in Tokenizer::new(data) {
+            match token {
+                Token::Start(tag) if tag.name == "sitemapindex" => index = true,
+                Token::Start(tag) if tag.name == "loc" => loc = Some(String::new()),
+                Token::Text(text) => if let Some(l) = loc.as_mut() {
+                    l.push_str(&text)
+                },
+                Token::End(name) if name == "loc" => if let Some(l) = loc.take() {
+                    let l = decode_entities(l.trim());
+                    if !l.is_empty() {
+                        locations.push(l);
+                    }
+                },
+                _ => (),
+            }
+        }
+
+        Self { index, locations }
+    }
+}
+
+fn is_url(location: &str) -> bool {
+    location.starts_with("http://") || location.starts_with("https://")
+}
+
+fn gunzip(data: Bytes) -> Result<Bytes> {
+    if data.starts_with(&[0x1f, 0x8b]) {
+        let mut decoded = Vec::new();
+        GzDecoder::new(&data[..]).read_to_end(&mut decoded)?;
+        Ok(Bytes::from(decoded))
+    } else {
+        Ok(data)
+    }
+}
+
+async fn read_sitemap(client: &Client, location: &str) -> Result<Bytes> {
+    let data = if is_url(location) {
+        client.get(location).send().await?.error_for_status()?.bytes().await?
+    } else {
+        Bytes::from(fs::read(location)?)
+    };
+    gunzip(data)
+}
+
+// every page url listed by the sitemap at `root`, following nested indexes
+pub async fn collect_pages(client: &Client, root: &str) -> Result<Vec<String>> {
+    let mut pending = vec![String::from(root)];
+    let mut visited = HashSet::new();
+    let mut pages = Vec::new();
+
+    while let Some(location) = pending.pop() {
+        if visited.len() >= MAX_SITEMAPS || !visited.insert(location.clone()) {
+            continue;
+        }
+
+        match read_sitemap(client, &location).await {
+            Ok(data) => {
+                let sitemap = Sitemap::parse(&data);
+                if sitemap.index {
+                    pending.extend(sitemap.locations.into_iter().rev());
+                } else {
+                    pages.extend(sitemap.locations);
+                }
+            },
+            Err(e) if location == root => return Err(e),
+            Err(e) => println!("{location}: {e}"),
+        }
+    }
+
+    Ok(pages)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use flate2::write::GzEncoder;
+    use flate2::Compression;
+
+    fn urlset(urls: &[&str]) -> String {
+        let locs: String = urls.iter().map(|u| format!("<url><loc>{u}</loc></url>")).collect();
+        format!("<?xml version=\"1.0\"?><urlset xmlns=\"http://www.sitemaps.org/schemas/sitemap/0.9\">{locs}</urlset>")
+    }
+
+    fn index(sitemaps: &[String]) -> String {
+        let locs: String = sitemaps.iter().map(|s| format!("<sitemap><loc>{s}</loc></sitemap>")).collect();
+        format!("<?xml version=\"1.0\"?><sitemapindex>{locs}</sitemapindex>")
+    }
+
+    #[test]
+    fn reads_locations_of_a_urlset_and_an_index() {
+        let sitemap = Sitemap::parse(urlset(&["https://a.edu/x?a=1&amp;b=2", " https://a.edu/y "]).as_bytes());
+        assert!(!sitemap.index);
+        assert_eq!(sitemap.locations, ["https://a.edu/x?a=1&b=2", "https://a.edu/y"]);
+
+        let sitemap = Sitemap::parse(index(&[String::from("https://a.edu/s.xml")]).as_bytes());
+        assert!(sitemap.index);
+        assert_eq!(sitemap.locations, ["https://a.edu/s.xml"]);
+    }
+
+    #[test]
+    fn gunzips_only_gzip_data() {
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(b"<urlset/>").unwrap();
+        let zipped = Bytes::from(encoder.finish().unwrap());
+        assert_eq!(gunzip(zipped).unwrap(), Bytes::from("<urlset/>"));
+        assert_eq!(gunzip(Bytes::from("<urlset/>")).unwrap(), Bytes::from("<urlset/>"));
+    }
+
+    #[tokio::test]
+    async fn follows_nested_indexes_and_gzipped_sitemaps() {
+        let dir = std::env::temp_dir().join(format!("web_migration-{}-sitemap/", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let at = |name: &str| String::from(dir.join(name).to_str().unwrap());
+
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(urlset(&["https://a.edu/a1", "https://a.edu/a2"]).as_bytes()).unwrap();
+        fs::write(at("a.xml.gz"), encoder.finish().unwrap()).unwrap();
+        fs::write(at("b.xml"), urlset(&["https://a.edu/b1"])).unwrap();
+        // the nested index leads back to the root, which is not read twice
+        fs::write(at("nested.xml"), index(&[at("b.xml"), at("root.xml"), at("missing.xml")])).unwrap();
+        fs::write(at("root.xml"), index(&[at("a.xml.gz"), at("nested.xml")])).unwrap();
+
+        let pages = collect_pages(&Client::new(), &at("root.xml")).await.unwrap();
+        assert_eq!(pages, ["https://a.edu/a1", "https://a.edu/a2", "https://a.edu/b1"]);
+        assert!(collect_pages(&Client::new(), &at("missing.xml")).await.is_err());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
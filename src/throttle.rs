@@ -0,0 +1,57 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use reqwest::Url;
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+use tokio::time::Instant;
+
+// caps requests in flight per host, and spaces out requests across all hosts
+pub struct Throttle {
+    concurrency: usize,
+    interval: Duration,
+    next: Mutex<Instant>,
+    hosts: Mutex<HashMap<String, Arc<Semaphore>>>,
+}
+
+impl Throttle {
+    // `rate` is requests per second, 0 for no limit. a crawl delay slows it further
+    pub fn new(concurrency: usize, rate: usize, crawl_delay: Option<Duration>) -> Self {
+        let interval = match rate {
+            0 => Duration::ZERO,
+            r => Duration::from_secs(1) / r as u32,
+        };
+
+        Self {
+            concurrency: concurrency.max(1),
+            interval: interval.max(crawl_delay.unwrap_or_default()),
+            next: Mutex::new(Instant::now()),
+            hosts: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn host(&self, url: &Url) -> Arc<Semaphore> {
+        let mut hosts = self.hosts.lock().unwrap();
+        hosts
+            .entry(String::from(url.host_str().unwrap_or_default()))
+            .or_insert_with(|| Arc::new(Semaphore::new(self.concurrency)))
+            .clone()
+    }
+
+    // hold the permit for as long as the request is in flight
+    pub async fn acquire(&self, url: &Url) -> OwnedSemaphorePermit {
+        let permit = self.host(url)
+            .acquire_owned()
+            .await
+            .expect("host semaphores are never closed");
+
+        let at = {
+            let mut next = self.next.lock().unwrap();
+            let at = (*next).max(Instant::now());
+            *next = at + self.interval;
+            at
+        };
+        tokio::time::sleep_until(at).await;
+
+        permit
+    }
+}